@@ -0,0 +1,74 @@
+//! Compares sync and async servers.
+//! Opens number of idle connections and measures how long it takes
+//! a single active client to make the given number of round trips.
+//!
+//! Usage:
+//!     cargo run --release --example bench --features async-server -- <sync|async> <idle> <round trips> 2>/dev/null
+use std::{
+    env,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Instant,
+};
+
+use glib::comm::{Server, ShutdownHandle};
+use glib::config::ServerConfig;
+
+fn start(backend: &str, config: ServerConfig) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<()>) {
+    match backend {
+        "sync" => {
+            let mut server = Server::from_config(config);
+            let addr = server.local_addr().unwrap();
+            let shutdown = server.shutdown_handle();
+            (addr, shutdown, thread::spawn(move || server.run()))
+        }
+        #[cfg(feature = "async-server")]
+        "async" => {
+            let mut server = glib::comm::AsyncServer::from_config(config);
+            let addr = server.local_addr().unwrap();
+            let shutdown = server.shutdown_handle();
+            (addr, shutdown, thread::spawn(move || server.run()))
+        }
+        other => panic!("unknown backend {}", other),
+    }
+}
+
+fn round_trip(stream: &mut TcpStream) {
+    let mut ping = b"RG".to_vec();
+    ping.extend(&1u32.to_le_bytes());
+    ping.extend(&0u32.to_le_bytes());
+    stream.write_all(&ping).unwrap();
+    let mut pong = [0; 10];
+    stream.read_exact(&mut pong).unwrap();
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let backend = args.get(1).map(String::as_str).unwrap_or("sync");
+    let idle: usize = args.get(2).map_or(100, |v| v.parse().unwrap());
+    let trips: usize = args.get(3).map_or(10_000, |v| v.parse().unwrap());
+
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.worker_threads = idle + 1;
+    config.pending_connections = idle + 1;
+    let (addr, shutdown, thread) = start(backend, config);
+
+    let started = Instant::now();
+    let mut idle_clients: Vec<TcpStream> = (0..idle).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    for client in idle_clients.iter_mut() {
+        round_trip(client);
+    }
+    println!("{}: {} connections opened in {:?}", backend, idle, started.elapsed());
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    let started = Instant::now();
+    for _ in 0..trips {
+        round_trip(&mut client);
+    }
+    println!("{}: {} round trips in {:?}", backend, trips, started.elapsed());
+
+    shutdown.trigger();
+    drop(idle_clients);
+    thread.join().unwrap();
+}
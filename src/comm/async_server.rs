@@ -0,0 +1,466 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use mio::{
+    net::{TcpListener, TcpStream},
+    Events, Interest, Poll, Token,
+};
+
+use crate::comm::{
+    connection::{self, DisconnectReason, MSG_BATCH_LEN},
+    errors, framing, handlers,
+    handlers::responses,
+    push, shutdown, Response, ShutdownHandle,
+};
+use crate::config;
+
+const LISTENER: Token = Token(usize::MAX);
+
+/// State of a single client connection
+/// handled by the event loop.
+struct Connection {
+    stream: TcpStream,
+    context: connection::Context,
+    /// Bytes read but not yet assembled into a message.
+    decoder: framing::Decoder,
+    /// Serialized responses waiting to be written.
+    outbox: Vec<u8>,
+    /// Messages the server pushes on its own,
+    /// moved to the outbox between the requests.
+    pushes: push::PushQueue,
+    /// Connection is closed for the given reason
+    /// as soon as the outbox is flushed.
+    closing: Option<DisconnectReason>,
+    /// When the last complete message was received.
+    last_message: Instant,
+    /// When the first byte of the message in progress was received.
+    message_started: Option<Instant>,
+    /// Since when the client doesn't receive what is in the outbox.
+    write_blocked_since: Option<Instant>,
+}
+
+/// Event driven server handling all of the connections
+/// on a single thread. Uses the same framing and
+/// request dispatcher as the Server, so they can be used
+/// interchangeably.
+pub struct AsyncServer {
+    listener: TcpListener,
+    poll: Poll,
+    req_dispatcher: Arc<RwLock<handlers::Dispatcher>>,
+    connections: HashMap<Token, Connection>,
+    settings: connection::Settings,
+    shutdown: ShutdownHandle,
+    grace_period: Duration,
+    conn_count: usize,
+}
+
+impl AsyncServer {
+    /// Creates new server instance.
+    /// Opens file from the provided path.
+    /// Then reads server configuration from it.
+    pub fn new(filename: String) -> AsyncServer {
+        eprintln!(
+            "[{:^15}]: Creating async server from file {}.",
+            "Initialization", filename
+        );
+        let config = config::ServerConfig::from_file(filename.as_str()).unwrap();
+        Self::from_config(config)
+    }
+
+    /// Creates new server instance from the already read configuration.
+    pub fn from_config(config: config::ServerConfig) -> AsyncServer {
+        let addr: SocketAddr = config.to_string().parse().unwrap();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        let poll = Poll::new().unwrap();
+        poll.registry()
+            .register(&mut listener, LISTENER, Interest::READABLE)
+            .unwrap();
+        eprintln!("[{:^15}]: Created.", "Initialization");
+        AsyncServer {
+            listener,
+            poll,
+            req_dispatcher: Arc::new(RwLock::new(handlers::init::new_dispatcher(&config))),
+            connections: HashMap::new(),
+            settings: connection::Settings::from(&config),
+            shutdown: ShutdownHandle::new(),
+            grace_period: Duration::from_millis(config.shutdown_grace_ms),
+            conn_count: 0,
+        }
+    }
+
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns handle which can be used to stop the server
+    /// from another thread.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Runs the event loop until the shutdown is triggered
+    /// and all of the connections are closed.
+    pub fn run(&mut self) {
+        eprintln!("[{:^15}]: Staring listening.", "AsyncServer");
+        let mut events = Events::with_capacity(1024);
+        while !self.shutdown.is_triggered() {
+            self.poll_once(&mut events);
+        }
+        eprintln!("[{:^15}]: Shutting down.", "AsyncServer");
+        if let Err(err) = self.poll.registry().deregister(&mut self.listener) {
+            eprintln!(
+                "[{:^15}]: Error while closing the listener! {}",
+                "AsyncServer", err
+            );
+        }
+        let deadline = Instant::now() + self.grace_period;
+        while !self.connections.is_empty() && Instant::now() < deadline {
+            self.say_goodbye_to_idle();
+            self.poll_once(&mut events);
+        }
+        if !self.connections.is_empty() {
+            eprintln!(
+                "[{:^15}]: Grace period exceeded. Severing {} connections.",
+                "AsyncServer",
+                self.connections.len()
+            );
+            let tokens: Vec<Token> = self.connections.keys().cloned().collect();
+            for token in tokens {
+                self.close(token, DisconnectReason::ServerClosed);
+            }
+        }
+        eprintln!("[{:^15}]: Stopped.", "AsyncServer");
+    }
+
+    fn poll_once(&mut self, events: &mut Events) {
+        if let Err(err) = self.poll.poll(events, Some(shutdown::POLL_INTERVAL)) {
+            if err.kind() != io::ErrorKind::Interrupted {
+                eprintln!("[{:^15}]: Error while polling! {}", "AsyncServer", err);
+            }
+            return;
+        }
+        self.handle_events(events);
+        self.enforce_timeouts();
+        self.tick();
+        self.deliver_pushes();
+    }
+
+    fn handle_events(&mut self, events: &Events) {
+        for event in events.iter() {
+            match event.token() {
+                LISTENER => self.accept_all(),
+                token => {
+                    if event.is_readable() {
+                        self.handle_readable(token);
+                    }
+                    self.flush(token);
+                }
+            }
+        }
+    }
+
+    fn accept_all(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, _)) => {
+                    let token = Token(self.conn_count);
+                    if let Err(err) =
+                        self.poll
+                            .registry()
+                            .register(&mut stream, token, Interest::READABLE)
+                    {
+                        eprintln!(
+                            "[{:^15}]: Could not set up the connection! {}",
+                            "AsyncServer", err
+                        );
+                        continue;
+                    }
+                    eprintln!("[{:^15}]: New connection established.", "AsyncServer");
+                    let context = connection::Context::new(self.conn_count);
+                    let pushes = match self.req_dispatcher.read() {
+                        Ok(dispatcher) => dispatcher.connected(&context),
+                        Err(err) => {
+                            eprintln!(
+                                "[{:^15}]: Could not open push queue! {}",
+                                "AsyncServer", err
+                            );
+                            push::PushQueue::default()
+                        }
+                    };
+                    self.connections.insert(
+                        token,
+                        Connection {
+                            stream,
+                            context,
+                            decoder: framing::Decoder::new(self.settings),
+                            outbox: Vec::new(),
+                            pushes,
+                            closing: None,
+                            last_message: Instant::now(),
+                            message_started: None,
+                            write_blocked_since: None,
+                        },
+                    );
+                    self.conn_count += 1;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    eprintln!(
+                        "[{:^15}]: Error while accepting connection! {}",
+                        "AsyncServer", err
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reads all available bytes and handles every message
+    /// that was fully assembled.
+    /// Messages are handled after each read, so the oversized
+    /// ones are rejected before they are buffered.
+    fn handle_readable(&mut self, token: Token) {
+        let conn = match self.connections.get_mut(&token) {
+            Some(val) => val,
+            None => return,
+        };
+        let dispatcher = match self.req_dispatcher.read() {
+            Ok(guard) => guard,
+            Err(err) => {
+                let err = errors::InternalServerError(Box::from(err.to_string()));
+                conn.send(Box::new(responses::Error::new(&err)));
+                conn.closing = Some(DisconnectReason::ServerClosed);
+                return;
+            }
+        };
+        let mut buffer = [0; MSG_BATCH_LEN];
+        while conn.closing.is_none() {
+            match conn.stream.read(&mut buffer) {
+                Ok(0) => {
+                    eprintln!(
+                        "[{:^12}[{}]]: Connection severed!",
+                        "AsyncConn", conn.context.id
+                    );
+                    drop(dispatcher);
+                    self.close(token, DisconnectReason::ClientLeft);
+                    return;
+                }
+                Ok(n) => {
+                    conn.decoder.extend(&buffer[..n]);
+                    conn.handle_messages(&dispatcher);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    eprintln!(
+                        "[{:^12}[{}]]: Error while reading \"{}\"",
+                        "AsyncConn", conn.context.id, err
+                    );
+                    drop(dispatcher);
+                    self.close(token, DisconnectReason::ClientLeft);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Lets the games whose planning time ran out resolve their turns.
+    fn tick(&self) {
+        match self.req_dispatcher.read() {
+            Ok(dispatcher) => dispatcher.tick(Instant::now()),
+            Err(err) => eprintln!(
+                "[{:^15}]: Could not resolve turns! {}",
+                "AsyncServer", err
+            ),
+        }
+    }
+
+    /// Moves pushed messages to the outboxes of their connections.
+    fn deliver_pushes(&mut self) {
+        let mut pushed = Vec::new();
+        for (token, conn) in self.connections.iter_mut() {
+            if conn.closing.is_some() {
+                continue;
+            }
+            let frames = conn.pushes.take();
+            if frames.is_empty() {
+                continue;
+            }
+            for frame in frames {
+                conn.outbox.extend(frame);
+            }
+            pushed.push(*token);
+        }
+        for token in pushed {
+            self.flush(token);
+        }
+    }
+
+    /// Sends Timeout error to the clients that were silent for too long
+    /// and drops the ones that don't receive what is sent to them.
+    fn enforce_timeouts(&mut self) {
+        let now = Instant::now();
+        let settings = self.settings;
+        let mut timed_out = Vec::new();
+        let mut unresponsive = Vec::new();
+        for (token, conn) in self.connections.iter_mut() {
+            if let Some(blocked) = conn.write_blocked_since {
+                if now.saturating_duration_since(blocked) > settings.write_timeout {
+                    unresponsive.push(*token);
+                    continue;
+                }
+            }
+            if conn.closing.is_some() {
+                continue;
+            }
+            if let Err(err) =
+                settings.check_timeout(conn.last_message, conn.message_started, now)
+            {
+                eprintln!(
+                    "[{:^12}[{}]]: {}. Disconnecting.",
+                    "AsyncConn", conn.context.id, err
+                );
+                conn.send(Box::new(responses::Error::new(
+                    &errors::BadRequestError::from(err),
+                )));
+                conn.closing = Some(DisconnectReason::TimedOut);
+                timed_out.push(*token);
+            }
+        }
+        for token in unresponsive {
+            self.close(token, DisconnectReason::TimedOut);
+        }
+        for token in timed_out {
+            self.flush(token);
+        }
+    }
+
+    /// Drops the connection and reports it to the dispatcher.
+    fn close(&mut self, token: Token, reason: DisconnectReason) {
+        let conn = match self.connections.remove(&token) {
+            Some(val) => val,
+            None => return,
+        };
+        eprintln!(
+            "[{:^12}[{}]]: Connection closed ({:?}).",
+            "AsyncConn", conn.context.id, reason
+        );
+        match self.req_dispatcher.read() {
+            Ok(dispatcher) => dispatcher.disconnected(&conn.context, reason),
+            Err(err) => eprintln!(
+                "[{:^12}[{}]]: Could not report disconnection! {}",
+                "AsyncConn", conn.context.id, err
+            ),
+        }
+    }
+
+    /// Writes as much of the outbox as the socket accepts.
+    /// Waits for the socket to become writable if something is left.
+    fn flush(&mut self, token: Token) {
+        let conn = match self.connections.get_mut(&token) {
+            Some(val) => val,
+            None => return,
+        };
+        while !conn.outbox.is_empty() {
+            match conn.stream.write(&conn.outbox) {
+                Ok(0) => break,
+                Ok(n) => {
+                    conn.outbox.drain(..n);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    eprintln!(
+                        "[{:^12}[{}]]: Error while sending the response \"{}\"",
+                        "AsyncConn", conn.context.id, err
+                    );
+                    self.close(token, DisconnectReason::ClientLeft);
+                    return;
+                }
+            }
+        }
+        if conn.outbox.is_empty() {
+            conn.write_blocked_since = None;
+            if let Some(reason) = conn.closing {
+                self.close(token, reason);
+                return;
+            }
+        } else if conn.write_blocked_since.is_none() {
+            conn.write_blocked_since = Some(Instant::now());
+        }
+        let interest = if conn.outbox.is_empty() {
+            Interest::READABLE
+        } else {
+            Interest::READABLE | Interest::WRITABLE
+        };
+        if let Err(err) = self
+            .poll
+            .registry()
+            .reregister(&mut conn.stream, token, interest)
+        {
+            eprintln!(
+                "[{:^12}[{}]]: Error while registering interest \"{}\"",
+                "AsyncConn", conn.context.id, err
+            );
+        }
+    }
+
+    /// Sends Goodbye to every connection that is not
+    /// in the middle of sending a request.
+    fn say_goodbye_to_idle(&mut self) {
+        let idle: Vec<Token> = self
+            .connections
+            .iter_mut()
+            .filter(|(_, conn)| conn.decoder.is_empty() && conn.closing.is_none())
+            .map(|(token, conn)| {
+                conn.send(Box::new(responses::Goodbye {}));
+                conn.closing = Some(DisconnectReason::ServerClosed);
+                *token
+            })
+            .collect();
+        for token in idle {
+            self.flush(token);
+        }
+    }
+}
+
+impl Connection {
+    /// Dispatches every complete message from the decoder
+    /// and queues their responses.
+    fn handle_messages(&mut self, dispatcher: &handlers::Dispatcher) {
+        let now = Instant::now();
+        while self.closing.is_none() {
+            match self.decoder.next_frame() {
+                Ok(Some(raw)) => {
+                    self.last_message = now;
+                    self.message_started = None;
+                    let resp = match dispatcher.dispatch_from_raw(raw, &mut self.context) {
+                        Ok(resp) => resp,
+                        Err(err) => Box::new(responses::Error::new(&err)),
+                    };
+                    self.send(resp);
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    // Framing is lost, there is no way to find the next message.
+                    self.send(Box::new(responses::Error::new(&err)));
+                    self.closing = Some(DisconnectReason::ProtocolError);
+                }
+            }
+        }
+        if self.message_started.is_none() && !self.decoder.is_empty() {
+            self.message_started = Some(now);
+        }
+    }
+
+    fn send(&mut self, resp: Box<dyn Response>) {
+        self.outbox
+            .extend(connection::Handler::response_as_bytes(resp));
+    }
+}
@@ -1,12 +1,17 @@
 use std::{
     convert::From,
-    io::{Read, Write},
-    iter::FromIterator,
-    net::TcpStream,
+    error::Error,
+    io::{self, Read, Write},
+    net::{self, TcpStream},
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
-use crate::comm::{errors, handlers, MessageRaw, Response};
+use crate::comm::{
+    errors, framing, handlers, handlers::responses, identity::PlayerId, push, MessageRaw,
+    Response, ShutdownHandle,
+};
+use crate::config;
 
 pub const SKEY: &[u8; MSG_SKEY_FIELD_LEN] = b"RG";
 pub const MSG_BATCH_LEN: usize = 512;
@@ -15,111 +20,357 @@ pub const MSG_SKEY_FIELD_LEN: usize = 2;
 pub const MSG_ID_FIELD_LEN: usize = 4;
 pub const MSG_HEADER_LEN: usize = MSG_SKEY_FIELD_LEN + MSG_LEN_FIELD_LEN + MSG_ID_FIELD_LEN;
 
+/// Stage of the connection's lifetime.
+/// Connection advances Connected -> Greeted -> Authenticated -> InLobby -> InGame
+/// and every request is accepted only in some of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    /// Handshake not done yet.
+    Connected,
+    /// Hello was welcomed.
+    Greeted,
+    /// Client proved which player it is.
+    Authenticated,
+    /// Player sits in one of the lobbies.
+    InLobby,
+    /// Player takes part in the running game.
+    InGame,
+}
+
+impl State {
+    /// Checks if the connection acts for the authenticated player.
+    pub fn is_authenticated(&self) -> bool {
+        !matches!(self, State::Connected | State::Greeted)
+    }
+
+    /// Every state, for requests accepted at any time.
+    pub const ANY: &'static [State] = &[
+        State::Connected,
+        State::Greeted,
+        State::Authenticated,
+        State::InLobby,
+        State::InGame,
+    ];
+}
+
 #[derive(Clone)]
 pub struct Context {
     pub id: usize,
-    pub initialized: bool,
+    pub state: State,
+    /// Protocol version agreed on in the handshake.
+    pub protocol_version: u32,
+    /// Name the client introduced itself with.
+    pub client_name: String,
+    /// Player the connection acts for, once authenticated.
+    pub player: Option<PlayerId>,
 }
 
 impl Context {
     pub fn new(conn_id: usize) -> Context {
         Context {
             id: conn_id,
-            initialized: false,
+            state: State::Connected,
+            protocol_version: 0,
+            client_name: String::new(),
+            player: None,
+        }
+    }
+}
+
+/// Why the connection was closed.
+/// Passed to the disconnect hooks, so the game layer
+/// can tell the players that left from the ones that are gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Client closed the connection.
+    ClientLeft,
+    /// Client missed the heartbeat or didn't finish the message in time.
+    TimedOut,
+    /// Client sent something that could not be read.
+    ProtocolError,
+    /// Server is shutting down or failed internally.
+    ServerClosed,
+}
+
+impl DisconnectReason {
+    /// Classifies error after which the connection could not be read anymore.
+    pub fn from_read_error(err: &errors::BadRequestError) -> DisconnectReason {
+        if err.is_connection_severed() {
+            DisconnectReason::ClientLeft
+        } else if err.is_timeout() {
+            DisconnectReason::TimedOut
+        } else {
+            DisconnectReason::ProtocolError
+        }
+    }
+}
+
+/// Connection limits shared by all of the connections.
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// Maximum length of the message, header included.
+    pub max_message_len: usize,
+    /// How long the client can stay silent between the messages.
+    /// Clients keep the connection alive by sending Ping.
+    pub idle_timeout: Duration,
+    /// How long the client has to finish the started message.
+    pub read_timeout: Duration,
+    /// How long writing a single response can block.
+    pub write_timeout: Duration,
+}
+
+impl Settings {
+    /// Checks if the message of the given length can be accepted.
+    pub fn check_message_len(&self, full_msg_len: usize) -> Result<(), errors::MessageTooLong> {
+        if full_msg_len > self.max_message_len {
+            return Err(errors::MessageTooLong {
+                max: self.max_message_len,
+                actual: full_msg_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks if the client waited too long with the next message.
+    /// Message in progress is given read timeout counted from its first byte,
+    /// otherwise the idle timeout since the last message applies.
+    pub fn check_timeout(
+        &self,
+        last_message: Instant,
+        message_started: Option<Instant>,
+        now: Instant,
+    ) -> Result<(), errors::Timeout> {
+        let (since, after, idle) = match message_started {
+            Some(started) => (started, self.read_timeout, false),
+            None => (last_message, self.idle_timeout, true),
+        };
+        if now.saturating_duration_since(since) > after {
+            return Err(errors::Timeout { after, idle });
+        }
+        Ok(())
+    }
+}
+
+impl From<&config::ServerConfig> for Settings {
+    fn from(config: &config::ServerConfig) -> Self {
+        Settings {
+            max_message_len: config.max_message_len,
+            idle_timeout: Duration::from_millis(config.idle_timeout_ms),
+            read_timeout: Duration::from_millis(config.read_timeout_ms),
+            write_timeout: Duration::from_millis(config.write_timeout_ms),
         }
     }
 }
 
 pub struct Handler {
     context: Context,
+    settings: Settings,
     req_handlers: Arc<RwLock<handlers::Dispatcher>>,
+    shutdown: ShutdownHandle,
 }
 
 impl Handler {
-    /// Initializes new connection handler. 
-    /// Where context is initial connection context.
+    /// Initializes new connection handler.
+    /// Where context is initial connection context,
+    /// settings are limits imposed on the connection.
     /// And req_handlers is a reader mutex on request dispatcher so
-    /// its not cloned each new connection and not blocked 
+    /// its not cloned each new connection and not blocked
     /// as its only used as a const reference.
-    pub fn new(context: Context, req_handlers: Arc<RwLock<handlers::Dispatcher>>) -> Handler {
+    /// Shutdown is checked between the requests, once triggered
+    /// client is sent Goodbye message and the connection is closed.
+    pub fn new(
+        context: Context,
+        settings: Settings,
+        req_handlers: Arc<RwLock<handlers::Dispatcher>>,
+        shutdown: ShutdownHandle,
+    ) -> Handler {
         Handler {
             context,
+            settings,
             req_handlers,
+            shutdown,
         }
     }
 
-    // todo:
-    // has no meanings of stopping.
-    // communication channel on which we can check to see if we should close?
+    /// Returns id of the handled connection.
+    pub fn id(&self) -> usize {
+        self.context.id
+    }
+
+    /// Sends the error response and closes the connection
+    /// without handling any requests.
+    pub fn refuse(&self, mut stream: TcpStream, err: &(dyn Error + 'static)) {
+        self.write_error(err, &mut stream);
+        let _ = stream.shutdown(net::Shutdown::Both);
+    }
+
+    /// Reads requests from the stream and writes back responses
+    /// until the connection is closed or the server shuts down.
+    /// Stream should have read timeout set, otherwise the shutdown
+    /// and the idle clients will be noticed only after the next request arrives.
+    /// Once closed the dispatcher's disconnect hooks are run.
     pub fn handle_connection(&self, mut stream: TcpStream) {
         let mut ctx = self.context.clone();
+        let pushes = match self.req_handlers.read() {
+            Ok(guard) => guard.connected(&ctx),
+            Err(err) => {
+                eprintln!(
+                    "[{:^12}[{}]]: Could not open push queue! {}",
+                    "ConnHandler", &self.context.id, err
+                );
+                push::PushQueue::default()
+            }
+        };
+        let reason = self.serve(&mut stream, &mut ctx, &pushes);
+        eprintln!(
+            "[{:^12}[{}]]: Connection closed ({:?}).",
+            "ConnHandler", &self.context.id, reason
+        );
+        match self.req_handlers.read() {
+            Ok(guard) => guard.disconnected(&ctx, reason),
+            Err(err) => eprintln!(
+                "[{:^12}[{}]]: Could not report disconnection! {}",
+                "ConnHandler", &self.context.id, err
+            ),
+        }
+    }
+
+    fn serve(
+        &self,
+        stream: &mut TcpStream,
+        ctx: &mut Context,
+        pushes: &push::PushQueue,
+    ) -> DisconnectReason {
+        let mut decoder = framing::Decoder::new(self.settings);
         loop {
-            eprintln!(
-                "[{:^12}[{}]]: Trying to build message!",
-                "ConnHandler", &self.context.id
-            );
-            let raw = match self.try_mess_read(&mut stream) {
-                Some(val) => val,
-                None => return,
+            let raw = match self.try_mess_read(stream, &mut decoder, pushes) {
+                Ok(Some(val)) => val,
+                Ok(None) => {
+                    eprintln!(
+                        "[{:^12}[{}]]: Server is shutting down. Saying goodbye.",
+                        "ConnHandler", &self.context.id
+                    );
+                    self.write_response(Box::new(responses::Goodbye {}), stream);
+                    return DisconnectReason::ServerClosed;
+                }
+                Err(err) => {
+                    // After failed read we cannot tell where the next message
+                    // begins so the connection has to be closed.
+                    if !err.is_connection_severed() {
+                        self.write_error(&err, stream);
+                    }
+                    return DisconnectReason::from_read_error(&err);
+                }
             };
+            // Messages pushed before the request was read go first.
+            self.write_pushed(pushes, stream);
             match self.req_handlers.read() {
                 Ok(guard) => {
-                    match self.handle_request(raw, &(*guard), &mut ctx) {
-                        Some(resp) => self.write_response(resp, &mut stream),
-                        None => return,
-                    };
+                    let resp = self.handle_request(raw, &guard, ctx);
+                    self.write_response(resp, stream);
                 }
                 Err(err) => {
                     eprintln!(
                         "[{:^12}[{}]]: Error while getting a lock! {}",
                         "ConnHandler", &self.context.id, err
                     );
-                    return;
+                    let err = errors::InternalServerError(Box::from(err.to_string()));
+                    self.write_error(&err, stream);
+                    return DisconnectReason::ServerClosed;
                 }
             }
         }
     }
 
-    fn try_mess_read(&self, stream: &mut TcpStream) -> Option<MessageRaw> {
+    fn try_mess_read(
+        &self,
+        stream: &mut TcpStream,
+        decoder: &mut framing::Decoder,
+        pushes: &push::PushQueue,
+    ) -> Result<Option<MessageRaw>, errors::BadRequestError> {
         eprintln!(
             "[{:^12}[{}]]: Trying to build message!",
             "ConnHandler", &self.context.id
         );
-        let raw = match self.read_mess(stream) {
-            Ok(buffer) => buffer,
+        let raw = match self.read_mess(stream, decoder, pushes) {
+            Ok(Some(buffer)) => buffer,
+            Ok(None) => return Ok(None),
             Err(err) => {
                 eprintln!(
                     "[{:^12}[{}]]: Error while building message: \"{}\". Aborting...",
                     "ConnHandler", &self.context.id, err,
                 );
-                return None;
+                return Err(err);
             }
         };
         eprintln!(
             "[{:^12}[{}]]: Message assembled. Request parsing!",
             "ConnHandler", &self.context.id
         );
-        return Some(raw);
+        Ok(Some(raw))
     }
 
-    fn handle_request(&self, raw: MessageRaw, req_dispatcher: &handlers::Dispatcher, ctx: &mut Context) -> Option<Box<dyn Response>> {
+    /// Dispatches request to its handler.
+    /// If handling fails the Error response is returned instead,
+    /// so the connection can be kept open.
+    fn handle_request(
+        &self,
+        raw: MessageRaw,
+        req_dispatcher: &handlers::Dispatcher,
+        ctx: &mut Context,
+    ) -> Box<dyn Response> {
         match req_dispatcher.dispatch_from_raw(raw, ctx) {
-            // todo method to return error response from error
             Err(err) => {
                 eprintln!(
-                    "[{:^12}[{}]]: Error while handling request {:?}",
-                    "ConnHandler", &self.context.id, err
+                    "[{:^12}[{}]]: Error while handling request ({} fault) {:?}",
+                    "ConnHandler",
+                    &self.context.id,
+                    if err.is_client_error() { "client" } else { "server" },
+                    err
                 );
-                None
+                Box::new(responses::Error::new(&err))
             }
             Ok(resp) => {
                 eprintln!(
                     "[{:^12}[{}]]: Got response!",
                     "ConnHandler", &self.context.id
                 );
-                Some(resp)
+                resp
+            }
+        }
+    }
+
+    fn write_error(&self, err: &(dyn Error + 'static), stream: &mut TcpStream) {
+        self.write_response(Box::new(responses::Error::new(err)), stream);
+    }
+
+    /// Writes messages the server pushed to the connection.
+    fn write_pushed(&self, pushes: &push::PushQueue, stream: &mut TcpStream) {
+        let frames = pushes.take();
+        if frames.is_empty() {
+            return;
+        }
+        for frame in frames.iter() {
+            if let Err(err) = stream.write_all(&frame[..]) {
+                eprintln!(
+                    "[{:^12}[{}]]: Error while pushing the message \"{}\"",
+                    "ConnHandler", &self.context.id, err
+                );
+                return;
             }
         }
+        eprintln!(
+            "[{:^12}[{}]]: Pushed {} messages.",
+            "ConnHandler",
+            &self.context.id,
+            frames.len()
+        );
+        if let Err(err) = stream.flush() {
+            eprintln!(
+                "[{:^12}[{}]]: Error while flushing the stream \"{}\"",
+                "ConnHandler", &self.context.id, err
+            );
+        }
     }
 
     fn write_response(&self, resp: Box<dyn Response>, stream: &mut TcpStream) {
@@ -133,47 +384,57 @@ impl Handler {
                 "ConnHandler", &self.context.id, err
             ),
         }
-        stream.flush().unwrap();
+        if let Err(err) = stream.flush() {
+            eprintln!(
+                "[{:^12}[{}]]: Error while flushing the stream \"{}\"",
+                "ConnHandler", &self.context.id, err
+            );
+        }
     }
 
-    fn read_mess(&self, stream: &mut TcpStream) -> Result<MessageRaw, errors::BadRequestError> {
-        let mut raw = Vec::with_capacity(MSG_HEADER_LEN);
-
-        let mut header_parsed = false;
-        let mut full_msg_len = 0;
+    /// Reads whole message from the stream.
+    /// Bytes read past the message are kept in the decoder
+    /// for the following calls.
+    /// Messages pushed in the meantime are written
+    /// between the reads, so they never split a response.
+    /// Returns None if the shutdown was triggered
+    /// before any byte of the message arrived.
+    /// Fails with Timeout if the client is idle for too long
+    /// or doesn't finish the message in time.
+    fn read_mess(
+        &self,
+        stream: &mut TcpStream,
+        decoder: &mut framing::Decoder,
+        pushes: &push::PushQueue,
+    ) -> Result<Option<MessageRaw>, errors::BadRequestError> {
+        let waiting_since = Instant::now();
+        let mut message_started = if decoder.is_empty() {
+            None
+        } else {
+            Some(waiting_since)
+        };
         loop {
-            self.extend_raw_mess(&mut raw, stream)?;
-            if raw.len() >= MSG_HEADER_LEN && !header_parsed {
-                full_msg_len = self.read_header(&mut raw)?;
-                header_parsed = true;
+            if let Some(raw) = decoder.next_frame()? {
+                return Ok(Some(raw));
             }
-            if raw.len() == full_msg_len as usize && header_parsed {
-                eprintln!(
-                    "[{:^12}[{}]]: Read all the payload bytes.",
-                    "ConnHandler", &self.context.id
-                );
-                break;
-            } else if raw.len() > full_msg_len as usize && header_parsed {
-                eprintln!(
-                    "[{:^12}[{}]]: Read more than specified in payload len. Aborting...",
-                    "ConnHandler", &self.context.id
-                );
-                return Err(errors::BadRequestError::from(errors::ReadError::from(
-                    format!(
-                        "read more bytes than specified in mess len. 
-                            Expected: {}, Read: {}",
-                        full_msg_len,
-                        raw.len(),
-                    ),
-                )));
+            if decoder.is_empty() && self.shutdown.is_triggered() {
+                return Ok(None);
+            }
+            self.settings
+                .check_timeout(waiting_since, message_started, Instant::now())?;
+            self.write_pushed(pushes, stream);
+            self.extend_raw_mess(decoder, stream)?;
+            if message_started.is_none() && !decoder.is_empty() {
+                message_started = Some(Instant::now());
             }
         }
-        Ok(raw)
     }
 
+    /// Passes bytes read from the stream to the decoder.
+    /// Read timing out is not an error, in that case nothing is passed.
     fn extend_raw_mess(
         &self,
-        raw: &mut MessageRaw,
+        decoder: &mut framing::Decoder,
         stream: &mut TcpStream,
     ) -> Result<(), errors::BadRequestError> {
         let mut buffer = [0; MSG_BATCH_LEN];
@@ -191,49 +452,24 @@ impl Handler {
                         "[{:^12}[{}]]: Read {} bytes. Proceeding.",
                         "ConnHandler", &self.context.id, n
                     );
-                    raw.extend_from_slice(&buffer[0..n]);
+                    decoder.extend(&buffer[0..n]);
                     Ok(())
                 }
             },
+            Err(ref err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(())
+            }
             Err(err) => Err(errors::BadRequestError::from(errors::ReadError::from(
                 err.to_string(),
             ))),
         }
     }
 
-    fn read_header(&self, raw: &MessageRaw) -> Result<u32, errors::HeaderValidationError> {
-        eprintln!(
-            "[{:^12}[{}]]: Read sufficient number of bytes to parse header.",
-            "ConnHandler", &self.context.id
-        );
-        let mut full_msg_len = Self::parse_header(&raw[..])?;
-        full_msg_len += MSG_HEADER_LEN as u32;
-        eprintln!(
-            "[{:^12}[{}]]: Full msg is {} bytes. {} more bytes to read",
-            "ConnHandler",
-            &self.context.id,
-            full_msg_len,
-            full_msg_len as usize - raw.len()
-        );
-        Ok(full_msg_len)
-    }
-
-    fn parse_header(header: &[u8]) -> Result<u32, errors::HeaderValidationError> {
-        for i in 0..MSG_SKEY_FIELD_LEN {
-            if SKEY[i] != header[i] {
-                return Err(errors::HeaderValidationError {
-                    expected: Vec::from_iter(SKEY.iter().cloned()),
-                    actual: Vec::from_iter(header.iter().cloned()),
-                });
-            }
-        }
-        let beggining = MSG_SKEY_FIELD_LEN + MSG_ID_FIELD_LEN;
-        let mut payload_len: [u8; 4] = [0; 4];
-        payload_len.copy_from_slice(&header[beggining..beggining + MSG_LEN_FIELD_LEN]);
-        Ok(u32::from_le_bytes(payload_len))
-    }
-
-    fn response_as_bytes(resp: Box<dyn Response>) -> Vec<u8> {
+    /// Serializes response to the wire format, header included.
+    pub fn response_as_bytes(resp: Box<dyn Response>) -> Vec<u8> {
         let mut as_bytes = Vec::with_capacity(MSG_HEADER_LEN);
         for &ch in SKEY.iter() {
             as_bytes.push(ch);
@@ -248,3 +484,83 @@ impl Handler {
         as_bytes
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            max_message_len: 64,
+            idle_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(5),
+            write_timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn message_within_limit_is_accepted() {
+        let settings = settings();
+        assert!(settings.check_message_len(MSG_HEADER_LEN).is_ok());
+        assert!(settings.check_message_len(64).is_ok());
+    }
+
+    #[test]
+    fn message_over_limit_is_rejected() {
+        let settings = settings();
+        match settings.check_message_len(65) {
+            Err(errors::MessageTooLong { max: 64, actual: 65 }) => (),
+            _ => panic!("message over the limit should be rejected"),
+        }
+    }
+
+    #[test]
+    fn idle_client_times_out_after_idle_timeout() {
+        let settings = settings();
+        let last = Instant::now();
+        assert!(settings
+            .check_timeout(last, None, last + Duration::from_secs(30))
+            .is_ok());
+        match settings.check_timeout(last, None, last + Duration::from_secs(31)) {
+            Err(errors::Timeout { idle: true, .. }) => (),
+            _ => panic!("idle client should time out"),
+        }
+    }
+
+    #[test]
+    fn started_message_times_out_after_read_timeout() {
+        let settings = settings();
+        let last = Instant::now();
+        let started = last + Duration::from_secs(20);
+        assert!(settings
+            .check_timeout(last, Some(started), started + Duration::from_secs(5))
+            .is_ok());
+        match settings.check_timeout(last, Some(started), started + Duration::from_secs(6)) {
+            Err(errors::Timeout { idle: false, .. }) => (),
+            _ => panic!("unfinished message should time out"),
+        }
+    }
+
+    #[test]
+    fn disconnect_reason_follows_read_error() {
+        let severed = errors::BadRequestError::from(errors::ConnectionSevered {});
+        assert_eq!(
+            DisconnectReason::from_read_error(&severed),
+            DisconnectReason::ClientLeft
+        );
+        let timeout = errors::BadRequestError::from(errors::Timeout {
+            after: Duration::from_secs(1),
+            idle: true,
+        });
+        assert_eq!(
+            DisconnectReason::from_read_error(&timeout),
+            DisconnectReason::TimedOut
+        );
+        let unknown = errors::BadRequestError::from(errors::UnknownMessageId { id: 1 });
+        assert_eq!(
+            DisconnectReason::from_read_error(&unknown),
+            DisconnectReason::ProtocolError
+        );
+    }
+}
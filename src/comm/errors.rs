@@ -1,9 +1,123 @@
 use std::convert::From;
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 use fast_from_derive::{BadRequest, SimpleError};
 
+use crate::comm::{connection, lobby::LobbyError, MessageId};
+use crate::game::error::GameError;
+
+/// Stable error codes sent to the client in the Error response.
+/// 4xx codes are returned on malformed requests,
+/// 5xx codes on server failures, 6xx codes on requests
+/// violating game rules and 7xx codes on failed lobby requests.
+pub mod code {
+    pub const BAD_REQUEST: u32 = 400;
+    pub const HEADER_VALIDATION: u32 = 401;
+    pub const READ_ERROR: u32 = 402;
+    pub const CONNECTION_SEVERED: u32 = 403;
+    pub const UNKNOWN_MESSAGE: u32 = 404;
+    pub const MESSAGE_TOO_LONG: u32 = 405;
+    pub const UNSUPPORTED_VERSION: u32 = 406;
+    pub const TIMEOUT: u32 = 408;
+    pub const WRONG_STATE: u32 = 409;
+    pub const INVALID_TOKEN: u32 = 410;
+    pub const NAME_TAKEN: u32 = 411;
+
+    pub const INTERNAL_SERVER_ERROR: u32 = 500;
+    pub const SERVER_FULL: u32 = 503;
+
+    pub const NON_EXISTING_UNIT: u32 = 600;
+    pub const POSITION_OUTSIDE_THE_BOARD: u32 = 601;
+    pub const MOVE_OUTSIDE_UNITS_REACH: u32 = 602;
+    pub const UNIT_BLOCKED: u32 = 604;
+    pub const IMPASSABLE_TERRAIN: u32 = 605;
+
+    pub const INVALID_GAME_SETTINGS: u32 = 700;
+    pub const NO_SUCH_LOBBY: u32 = 701;
+    pub const LOBBY_FULL: u32 = 702;
+    pub const ALREADY_IN_LOBBY: u32 = 703;
+    pub const NOT_IN_LOBBY: u32 = 704;
+    pub const GAME_STARTED: u32 = 705;
+    pub const GAME_NOT_STARTED: u32 = 706;
+    pub const TURN_ENDED: u32 = 707;
+    pub const NO_SUCH_SCENARIO: u32 = 708;
+}
+
+/// Maps error to its code from the code module.
+/// Bad requests take the code of their cause if it is known,
+/// internal server errors are always reported as such.
+pub fn error_code(err: &(dyn Error + 'static)) -> u32 {
+    if let Some(err) = err.downcast_ref::<HandlerError>() {
+        return match err {
+            HandlerError::BadRequest(err) => error_code(err),
+            HandlerError::Internal(err) => error_code(err),
+        };
+    }
+    if let Some(err) = err.downcast_ref::<BadRequestError>() {
+        return specific_error_code(err.0.as_ref()).unwrap_or(code::BAD_REQUEST);
+    }
+    if err.is::<InternalServerError>() {
+        return code::INTERNAL_SERVER_ERROR;
+    }
+    specific_error_code(err).unwrap_or(code::INTERNAL_SERVER_ERROR)
+}
+
+fn specific_error_code(err: &(dyn Error + 'static)) -> Option<u32> {
+    if err.is::<HeaderValidationError>() {
+        Some(code::HEADER_VALIDATION)
+    } else if err.is::<ReadError>() {
+        Some(code::READ_ERROR)
+    } else if err.is::<ConnectionSevered>() {
+        Some(code::CONNECTION_SEVERED)
+    } else if err.is::<UnknownMessageId>() {
+        Some(code::UNKNOWN_MESSAGE)
+    } else if err.is::<MessageTooLong>() {
+        Some(code::MESSAGE_TOO_LONG)
+    } else if err.is::<UnsupportedVersion>() {
+        Some(code::UNSUPPORTED_VERSION)
+    } else if err.is::<Timeout>() {
+        Some(code::TIMEOUT)
+    } else if err.is::<WrongState>() {
+        Some(code::WRONG_STATE)
+    } else if err.is::<InvalidToken>() {
+        Some(code::INVALID_TOKEN)
+    } else if err.is::<NameTaken>() {
+        Some(code::NAME_TAKEN)
+    } else if err.is::<ServerFull>() {
+        Some(code::SERVER_FULL)
+    } else if let Some(err) = err.downcast_ref::<GameError>() {
+        Some(game_error_code(err))
+    } else {
+        err.downcast_ref::<LobbyError>().map(lobby_error_code)
+    }
+}
+
+fn lobby_error_code(err: &LobbyError) -> u32 {
+    match err {
+        LobbyError::InvalidSettings(..) => code::INVALID_GAME_SETTINGS,
+        LobbyError::NoSuchLobby(..) => code::NO_SUCH_LOBBY,
+        LobbyError::LobbyFull(..) => code::LOBBY_FULL,
+        LobbyError::AlreadyInLobby(..) => code::ALREADY_IN_LOBBY,
+        LobbyError::NotInLobby => code::NOT_IN_LOBBY,
+        LobbyError::GameStarted(..) => code::GAME_STARTED,
+        LobbyError::GameNotStarted(..) => code::GAME_NOT_STARTED,
+        LobbyError::TurnEnded(..) => code::TURN_ENDED,
+        LobbyError::NoSuchScenario(..) => code::NO_SUCH_SCENARIO,
+    }
+}
+
+fn game_error_code(err: &GameError) -> u32 {
+    match err {
+        GameError::NonExistingUnit(..) => code::NON_EXISTING_UNIT,
+        GameError::PositionOutsideTheBoard(..) => code::POSITION_OUTSIDE_THE_BOARD,
+        GameError::MoveOutsideUnitsReach(..) => code::MOVE_OUTSIDE_UNITS_REACH,
+        GameError::UnitBlocked(..) => code::UNIT_BLOCKED,
+        GameError::ImpassableTerrain(..) => code::IMPASSABLE_TERRAIN,
+    }
+}
+
 /// General 400 status errors and some more (like connection severed).
 #[derive(Debug)]
 pub struct BadRequestError(pub Box<dyn Error>);
@@ -20,6 +134,26 @@ impl Error for BadRequestError {
     }
 }
 
+impl BadRequestError {
+    /// Checks if the error was caused by the client closing the connection.
+    pub fn is_connection_severed(&self) -> bool {
+        self.0.is::<ConnectionSevered>()
+    }
+
+    /// Checks if the error was caused by the client not sending
+    /// anything for too long.
+    pub fn is_timeout(&self) -> bool {
+        self.0.is::<Timeout>()
+    }
+}
+
+/// Game rules violated by the client's order.
+impl From<GameError> for BadRequestError {
+    fn from(err: GameError) -> Self {
+        BadRequestError(Box::new(err))
+    }
+}
+
 /// General 500 status errors.
 #[derive(Debug)]
 pub struct InternalServerError(pub Box<dyn Error>);
@@ -36,6 +170,57 @@ impl Error for InternalServerError {
     }
 }
 
+/// Returned from the request handlers.
+/// Keeps the original cause of the failure
+/// along with the information who is to blame for it.
+#[derive(Debug)]
+pub enum HandlerError {
+    /// Request was malformed or could not be fulfilled.
+    BadRequest(BadRequestError),
+    /// Server failed while handling a valid request.
+    Internal(InternalServerError),
+}
+
+impl HandlerError {
+    /// Checks if the client is to blame for the error.
+    pub fn is_client_error(&self) -> bool {
+        match self {
+            HandlerError::BadRequest(_) => true,
+            HandlerError::Internal(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HandlerError::BadRequest(err) => err.fmt(f),
+            HandlerError::Internal(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for HandlerError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            HandlerError::BadRequest(err) => Some(err),
+            HandlerError::Internal(err) => Some(err),
+        }
+    }
+}
+
+impl From<BadRequestError> for HandlerError {
+    fn from(err: BadRequestError) -> Self {
+        HandlerError::BadRequest(err)
+    }
+}
+
+impl From<InternalServerError> for HandlerError {
+    fn from(err: InternalServerError) -> Self {
+        HandlerError::Internal(err)
+    }
+}
+
 /// Returned if received request
 /// had invalid headers server key.
 #[derive(Debug, BadRequest, SimpleError)]
@@ -64,6 +249,133 @@ impl fmt::Display for ConnectionSevered {
     }
 }
 
+/// Returned when request's message id
+/// doesn't match any of the registered handlers.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct UnknownMessageId {
+    pub id: MessageId,
+}
+
+impl fmt::Display for UnknownMessageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Mess id ({}) doesn't match any of registered ones.",
+            self.id
+        )
+    }
+}
+
+/// Returned when message header announces
+/// more bytes than the server accepts.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct MessageTooLong {
+    pub max: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for MessageTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Message too long: (max {} bytes, got {})",
+            self.max, self.actual
+        )
+    }
+}
+
+/// Returned when request is not accepted
+/// in the current state of the connection,
+/// for example when game request is sent before the handshake.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct WrongState {
+    pub id: MessageId,
+    pub state: connection::State,
+}
+
+impl fmt::Display for WrongState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Mess id ({}) is not accepted in the {:?} state.",
+            self.id, self.state
+        )
+    }
+}
+
+/// Returned when the player token is malformed, unknown
+/// or doesn't belong to the player bound to the connection.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct InvalidToken;
+
+impl fmt::Display for InvalidToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid player token")
+    }
+}
+
+/// Returned when player tries to register
+/// under the name someone else already uses.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct NameTaken {
+    pub name: String,
+}
+
+impl fmt::Display for NameTaken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Player name \"{}\" is already taken", self.name)
+    }
+}
+
+/// Returned when client's protocol version
+/// is older than the oldest one the server speaks.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct UnsupportedVersion {
+    pub client: u32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Protocol version {} is not supported. Supported versions: {}-{}.",
+            self.client, self.min, self.max
+        )
+    }
+}
+
+/// Returned when client missed the heartbeat window
+/// or didn't finish the started message in time.
+#[derive(Debug, BadRequest, SimpleError)]
+pub struct Timeout {
+    pub after: Duration,
+    /// True if no message was being sent.
+    pub idle: bool,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.idle {
+            write!(f, "No message received for {:?}", self.after)
+        } else {
+            write!(f, "Message not completed within {:?}", self.after)
+        }
+    }
+}
+
+/// Returned when there is no free worker
+/// nor place in the queue for the new connection.
+#[derive(Debug, SimpleError)]
+pub struct ServerFull;
+
+impl fmt::Display for ServerFull {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Server is full, try again later")
+    }
+}
+
 #[derive(Debug, BadRequest, SimpleError)]
 pub struct ReadError {
     couse: String,
@@ -80,3 +392,117 @@ impl From<String> for ReadError {
         ReadError { couse }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn bad_request_takes_code_of_its_cause() {
+        let err = BadRequestError::from(UnknownMessageId { id: 5 });
+        assert_eq!(error_code(&err), code::UNKNOWN_MESSAGE);
+        let err = BadRequestError::from(ReadError::from(String::from("eof")));
+        assert_eq!(error_code(&err), code::READ_ERROR);
+    }
+
+    #[test]
+    fn bad_request_with_unknown_cause_is_generic() {
+        let err = BadRequestError(Box::from("something went wrong"));
+        assert_eq!(error_code(&err), code::BAD_REQUEST);
+    }
+
+    #[test]
+    fn internal_server_error_hides_its_cause() {
+        let err = InternalServerError(Box::new(ReadError::from(String::from("eof"))));
+        assert_eq!(error_code(&err), code::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn unsupported_version_has_its_own_code() {
+        let err = BadRequestError::from(UnsupportedVersion {
+            client: 0,
+            min: 1,
+            max: 1,
+        });
+        assert_eq!(error_code(&err), code::UNSUPPORTED_VERSION);
+    }
+
+    #[test]
+    fn identity_errors_have_their_own_codes() {
+        assert_eq!(
+            error_code(&BadRequestError::from(InvalidToken)),
+            code::INVALID_TOKEN
+        );
+        let err = BadRequestError::from(NameTaken {
+            name: String::from("alice"),
+        });
+        assert_eq!(error_code(&err), code::NAME_TAKEN);
+    }
+
+    #[test]
+    fn timeout_is_recognized() {
+        let err = BadRequestError::from(Timeout {
+            after: Duration::from_secs(1),
+            idle: true,
+        });
+        assert!(err.is_timeout());
+        assert!(!err.is_connection_severed());
+        assert_eq!(error_code(&err), code::TIMEOUT);
+    }
+
+    #[test]
+    fn server_full_has_its_own_code() {
+        assert_eq!(error_code(&ServerFull), code::SERVER_FULL);
+    }
+
+    #[test]
+    fn game_errors_have_their_own_codes() {
+        assert_eq!(
+            error_code(&GameError::NonExistingUnit(1)),
+            code::NON_EXISTING_UNIT
+        );
+        assert_eq!(
+            error_code(&GameError::PositionOutsideTheBoard(1, 1)),
+            code::POSITION_OUTSIDE_THE_BOARD
+        );
+        assert_eq!(
+            error_code(&GameError::MoveOutsideUnitsReach(1, 1)),
+            code::MOVE_OUTSIDE_UNITS_REACH
+        );
+        assert_eq!(
+            error_code(&GameError::UnitBlocked(1)),
+            code::UNIT_BLOCKED
+        );
+        assert_eq!(
+            error_code(&GameError::ImpassableTerrain(1, 1)),
+            code::IMPASSABLE_TERRAIN
+        );
+    }
+
+    #[test]
+    fn lobby_errors_have_their_own_codes() {
+        let err = BadRequestError::from(LobbyError::NoSuchLobby(1));
+        assert_eq!(error_code(&err), code::NO_SUCH_LOBBY);
+        let err = BadRequestError::from(LobbyError::GameStarted(1));
+        assert_eq!(error_code(&err), code::GAME_STARTED);
+        let err = BadRequestError::from(LobbyError::NoSuchScenario(String::from("duel")));
+        assert_eq!(error_code(&err), code::NO_SUCH_SCENARIO);
+    }
+
+    #[test]
+    fn handler_error_keeps_code_of_its_cause() {
+        let err = HandlerError::from(BadRequestError::from(UnknownMessageId { id: 1 }));
+        assert!(err.is_client_error());
+        assert_eq!(error_code(&err), code::UNKNOWN_MESSAGE);
+        let err = HandlerError::from(InternalServerError(Box::from("oops")));
+        assert!(!err.is_client_error());
+        assert_eq!(error_code(&err), code::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn severed_connection_is_recognized() {
+        assert!(BadRequestError::from(ConnectionSevered).is_connection_severed());
+        assert!(!BadRequestError::from(UnknownMessageId { id: 0 }).is_connection_severed());
+    }
+}
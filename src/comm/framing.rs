@@ -0,0 +1,178 @@
+use std::{iter::FromIterator, mem};
+
+use crate::comm::{
+    connection::{Settings, MSG_HEADER_LEN, MSG_ID_FIELD_LEN, MSG_LEN_FIELD_LEN, MSG_SKEY_FIELD_LEN, SKEY},
+    errors, MessageRaw,
+};
+
+/// Splits stream of bytes read from the connection into messages.
+/// Keeps bytes that do not form a complete message yet,
+/// so single read can yield any number of messages
+/// and a message can be spread across any number of reads.
+pub struct Decoder {
+    settings: Settings,
+    buffer: MessageRaw,
+}
+
+impl Decoder {
+    pub fn new(settings: Settings) -> Decoder {
+        Decoder {
+            settings,
+            buffer: Vec::with_capacity(MSG_HEADER_LEN),
+        }
+    }
+
+    /// Appends bytes read from the connection.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Checks if there are no bytes waiting to form a message.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns the first complete message or None if there is none yet.
+    /// Server key is validated as soon as its bytes arrive and the
+    /// message length as soon as the header is complete, so the invalid
+    /// message is rejected before its payload is buffered.
+    /// After an error the stream cannot be decoded any further.
+    pub fn next_frame(&mut self) -> Result<Option<MessageRaw>, errors::BadRequestError> {
+        validate_skey(&self.buffer)?;
+        if self.buffer.len() < MSG_HEADER_LEN {
+            return Ok(None);
+        }
+        let full_msg_len = parse_header(&self.buffer)? as usize + MSG_HEADER_LEN;
+        self.settings.check_message_len(full_msg_len)?;
+        if self.buffer.len() < full_msg_len {
+            return Ok(None);
+        }
+        let rest = self.buffer.split_off(full_msg_len);
+        Ok(Some(mem::replace(&mut self.buffer, rest)))
+    }
+}
+
+/// Checks whether bytes received so far match the server key.
+fn validate_skey(raw: &[u8]) -> Result<(), errors::HeaderValidationError> {
+    let received = &raw[..raw.len().min(MSG_SKEY_FIELD_LEN)];
+    if received != &SKEY[..received.len()] {
+        return Err(errors::HeaderValidationError {
+            expected: Vec::from_iter(SKEY.iter().cloned()),
+            actual: Vec::from_iter(received.iter().cloned()),
+        });
+    }
+    Ok(())
+}
+
+/// Validates the server key and returns payload length from the header.
+fn parse_header(header: &[u8]) -> Result<u32, errors::HeaderValidationError> {
+    validate_skey(header)?;
+    let beggining = MSG_SKEY_FIELD_LEN + MSG_ID_FIELD_LEN;
+    let mut payload_len: [u8; 4] = [0; 4];
+    payload_len.copy_from_slice(&header[beggining..beggining + MSG_LEN_FIELD_LEN]);
+    Ok(u32::from_le_bytes(payload_len))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::comm::errors::{code, error_code};
+    use std::time::Duration;
+
+    fn decoder() -> Decoder {
+        Decoder::new(Settings {
+            max_message_len: 64,
+            idle_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(5),
+            write_timeout: Duration::from_secs(5),
+        })
+    }
+
+    fn frame(id: u32, payload: &[u8]) -> MessageRaw {
+        let mut raw = SKEY.to_vec();
+        raw.extend(&id.to_le_bytes());
+        raw.extend(&(payload.len() as u32).to_le_bytes());
+        raw.extend_from_slice(payload);
+        raw
+    }
+
+    #[test]
+    fn empty_decoder_yields_nothing() {
+        let mut d = decoder();
+        assert!(d.next_frame().unwrap().is_none());
+        assert!(d.is_empty());
+    }
+
+    #[test]
+    fn partial_header_yields_nothing() {
+        let mut d = decoder();
+        d.extend(&frame(0, &[])[..5]);
+        assert!(d.next_frame().unwrap().is_none());
+        assert!(!d.is_empty());
+    }
+
+    #[test]
+    fn complete_frame_is_yielded() {
+        let mut d = decoder();
+        d.extend(&frame(3, &[1, 2, 3]));
+        assert_eq!(d.next_frame().unwrap().unwrap(), frame(3, &[1, 2, 3]));
+        assert!(d.is_empty());
+    }
+
+    #[test]
+    fn frame_spread_across_reads_is_assembled() {
+        let mut d = decoder();
+        let raw = frame(1, &[9; 20]);
+        for chunk in raw.chunks(3) {
+            assert!(d.next_frame().unwrap().is_none());
+            d.extend(chunk);
+        }
+        assert_eq!(d.next_frame().unwrap().unwrap(), raw);
+    }
+
+    #[test]
+    fn pipelined_frames_are_yielded_in_order() {
+        let mut d = decoder();
+        let mut raw = frame(0, &[]);
+        raw.extend(frame(1, &[7, 7]));
+        raw.extend(&frame(2, &[])[..4]);
+        d.extend(&raw);
+        assert_eq!(d.next_frame().unwrap().unwrap(), frame(0, &[]));
+        assert_eq!(d.next_frame().unwrap().unwrap(), frame(1, &[7, 7]));
+        assert!(d.next_frame().unwrap().is_none());
+        d.extend(&frame(2, &[])[4..]);
+        assert_eq!(d.next_frame().unwrap().unwrap(), frame(2, &[]));
+    }
+
+    #[test]
+    fn header_payload_len_is_parsed() {
+        let mut raw = SKEY.to_vec();
+        raw.extend(&7u32.to_le_bytes());
+        raw.extend(&u32::MAX.to_le_bytes());
+        assert_eq!(parse_header(&raw).unwrap(), u32::MAX);
+    }
+
+    #[test]
+    fn invalid_skey_is_rejected_after_two_bytes() {
+        let mut d = decoder();
+        d.extend(b"RX");
+        let err = d.next_frame().err().unwrap();
+        assert_eq!(error_code(&err), code::HEADER_VALIDATION);
+    }
+
+    #[test]
+    fn invalid_first_skey_byte_is_rejected_right_away() {
+        let mut d = decoder();
+        d.extend(b"X");
+        assert!(d.next_frame().is_err());
+    }
+
+    #[test]
+    fn too_long_frame_is_rejected_after_header() {
+        let mut d = decoder();
+        d.extend(&frame(0, &[0; 100])[..MSG_HEADER_LEN]);
+        let err = d.next_frame().err().unwrap();
+        assert_eq!(error_code(&err), code::MESSAGE_TOO_LONG);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::comm::{
+    connection,
+    connection::MSG_HEADER_LEN,
+    errors::{BadRequestError, HandlerError, ReadError},
+    handlers::{lock_error, requests, responses, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::Auth, responses::Authenticated> for Handler {
+    fn req_id() -> MessageId {
+        2
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Greeted]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Auth, BadRequestError> {
+        let name = String::from_utf8(raw[MSG_HEADER_LEN..].to_vec())
+            .map_err(|err| ReadError::from(format!("Player name is not utf-8: {}", err)))?;
+        Ok(requests::Auth { name })
+    }
+
+    /// Creates identity for the player and binds the connection to it.
+    fn handle_request(
+        req: requests::Auth,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::Authenticated, HandlerError> {
+        let identity = shared
+            .identities
+            .write()
+            .map_err(lock_error)?
+            .register(req.name)?;
+        eprintln!(
+            "[{:^15}]: {} authenticated as player {}",
+            "AuthHandler", identity.name, identity.player_id
+        );
+        ctx.player = Some(identity.player_id);
+        ctx.state = connection::State::Authenticated;
+        shared
+            .sessions
+            .write()
+            .map_err(lock_error)?
+            .open(identity.player_id, ctx);
+        Ok(responses::Authenticated {
+            player_id: identity.player_id,
+            token: identity.token(),
+        })
+    }
+}
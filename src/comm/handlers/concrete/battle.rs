@@ -0,0 +1,75 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{orders, payload, requests, responses, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::Battle, responses::OrderAccepted> for Handler {
+    fn req_id() -> MessageId {
+        11
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::InGame]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Battle, BadRequestError> {
+        let mut reader = payload::Reader::new(raw);
+        let req = requests::Battle {
+            unit_id: reader.u32()?,
+            target_id: reader.u32()?,
+        };
+        reader.finish()?;
+        Ok(req)
+    }
+
+    /// Ordered unit attacks the position of the target.
+    /// Orders of the target don't change.
+    fn handle_request(
+        req: requests::Battle,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::OrderAccepted, HandlerError> {
+        let unit_id = req.unit_id as usize;
+        let target_id = req.target_id as usize;
+        orders::order_unit(shared, ctx, unit_id, |game| {
+            game.attack_unit(unit_id, target_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::comm::handlers::orders::tests::{running_game, unit_state};
+    use crate::game::unit::{Category, State};
+
+    #[test]
+    fn target_keeps_its_orders() {
+        let (shared, mut ctx) = running_game();
+        let target_id = {
+            let mut lobbies = shared.lobbies.write().unwrap();
+            let game = lobbies.game_of_mut(0).unwrap().0;
+            let target_id = game.add_unit(1, (1, 2), Category::Knight).unwrap().id;
+            game.move_unit(target_id, (1, 4)).unwrap();
+            target_id
+        };
+        let req = requests::Battle {
+            unit_id: 0,
+            target_id: target_id as u32,
+        };
+        Handler::handle_request(req, &mut ctx, &shared).unwrap();
+        match unit_state(&shared, 0) {
+            State::Attack(1, 2) => (),
+            state => panic!("unit should attack the target, is {:?}", state),
+        }
+        match unit_state(&shared, target_id) {
+            State::Moving(1, 4) => (),
+            state => panic!("target should keep its orders, is {:?}", state),
+        }
+    }
+}
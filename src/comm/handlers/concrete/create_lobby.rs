@@ -0,0 +1,89 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{bound_player, lock_error, payload, requests, responses, Shared, SharedBuilder},
+    lobby::LobbyError,
+    MessageId, MessageRaw,
+};
+use crate::game::victory::Victory;
+
+pub struct Handler;
+
+impl SharedBuilder<requests::CreateLobby, responses::LobbyJoined> for Handler {
+    fn req_id() -> MessageId {
+        4
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Authenticated]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::CreateLobby, BadRequestError> {
+        let mut reader = payload::Reader::new(raw);
+        let mut req = requests::CreateLobby {
+            num_of_players: reader.u8()?,
+            board_size: (reader.u32()?, reader.u32()?),
+            victory: 0,
+            victory_turns: 0,
+            map_seed: None,
+        };
+        if reader.has_more() {
+            req.victory = reader.u8()?;
+            req.victory_turns = reader.u32()?;
+        }
+        if reader.has_more() {
+            req.map_seed = Some(reader.u64()?);
+        }
+        reader.finish()?;
+        Ok(req)
+    }
+
+    /// Opens the lobby and seats its creator in it.
+    /// Map is generated if the request carries its seed.
+    fn handle_request(
+        req: requests::CreateLobby,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::LobbyJoined, HandlerError> {
+        let player = bound_player(ctx)?;
+        let mut lobbies = shared.lobbies.write().map_err(lock_error)?;
+        let board_size = (req.board_size.0 as usize, req.board_size.1 as usize);
+        let victory = victory(req.victory, req.victory_turns, board_size)
+            .map_err(BadRequestError::from)?;
+        let lobby_id = match req.map_seed {
+            Some(seed) => lobbies.create_generated(req.num_of_players, board_size, victory, seed),
+            None => lobbies.create(req.num_of_players, board_size, victory),
+        }
+        .map_err(BadRequestError::from)?;
+        let seat = lobbies.host(lobby_id, player).map_err(BadRequestError::from)?;
+        match req.map_seed {
+            Some(seed) => eprintln!(
+                "[{:^15}]: Player {} opened lobby {} on map {}",
+                "LobbyHandler", player, lobby_id, seed
+            ),
+            None => eprintln!(
+                "[{:^15}]: Player {} opened lobby {}",
+                "LobbyHandler", player, lobby_id
+            ),
+        }
+        ctx.state = connection::State::InLobby;
+        Ok(responses::LobbyJoined { lobby_id, seat })
+    }
+}
+
+/// Decodes the victory condition of the request.
+/// Objective of the game is the board's centre.
+fn victory(kind: u8, turns: u32, (width, height): (usize, usize)) -> Result<Victory, LobbyError> {
+    match kind {
+        0 => Ok(Victory::LastStanding),
+        1 => Ok(Victory::Objectives {
+            fields: vec![(width / 2, height / 2)],
+            turns,
+        }),
+        2 => Ok(Victory::TurnLimit(turns)),
+        _ => Err(LobbyError::InvalidSettings(format!(
+            "unknown victory condition {}",
+            kind
+        ))),
+    }
+}
@@ -0,0 +1,45 @@
+use crate::comm::{
+    connection,
+    connection::MSG_HEADER_LEN,
+    errors::{BadRequestError, HandlerError, ReadError},
+    handlers::{bound_player, lock_error, requests, responses, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::CreateScenarioLobby, responses::LobbyJoined> for Handler {
+    fn req_id() -> MessageId {
+        14
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Authenticated]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::CreateScenarioLobby, BadRequestError> {
+        let name = String::from_utf8(raw[MSG_HEADER_LEN..].to_vec())
+            .map_err(|err| ReadError::from(format!("Scenario name is not utf-8: {}", err)))?;
+        Ok(requests::CreateScenarioLobby { name })
+    }
+
+    /// Opens the lobby playing the scenario and seats its creator in it.
+    /// Scenario is read before the lobbies are locked.
+    fn handle_request(
+        req: requests::CreateScenarioLobby,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::LobbyJoined, HandlerError> {
+        let player = bound_player(ctx)?;
+        let scenario = shared.scenarios.load(&req.name).map_err(BadRequestError::from)?;
+        let mut lobbies = shared.lobbies.write().map_err(lock_error)?;
+        let lobby_id = lobbies.create_from(scenario).map_err(BadRequestError::from)?;
+        let seat = lobbies.host(lobby_id, player).map_err(BadRequestError::from)?;
+        eprintln!(
+            "[{:^15}]: Player {} opened lobby {} playing {}",
+            "LobbyHandler", player, lobby_id, req.name
+        );
+        ctx.state = connection::State::InLobby;
+        Ok(responses::LobbyJoined { lobby_id, seat })
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Instant;
+
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{bound_player, lock_error, payload, requests, responses, turns, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::EndTurn, responses::TurnEnded> for Handler {
+    fn req_id() -> MessageId {
+        12
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::InGame]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::EndTurn, BadRequestError> {
+        payload::Reader::new(raw).finish()?;
+        Ok(requests::EndTurn {})
+    }
+
+    /// Turn is resolved once the last player ends it.
+    fn handle_request(
+        _req: requests::EndTurn,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::TurnEnded, HandlerError> {
+        let player = bound_player(ctx)?;
+        let (turn, report) = shared
+            .lobbies
+            .write()
+            .map_err(lock_error)?
+            .end_turn(player, Instant::now())
+            .map_err(BadRequestError::from)?;
+        if let Some(report) = report {
+            turns::announce(shared, &report)?;
+        }
+        Ok(responses::TurnEnded { turn })
+    }
+}
@@ -1,40 +1,90 @@
 use crate::comm::{
-    connection, 
+    connection,
     connection::MSG_HEADER_LEN,
-    errors::ReadError, 
-    MessageId, MessageRaw, 
-    handlers::{
-        requests,
-        responses,
-        DefaultBuilder
-    },
+    errors::{BadRequestError, HandlerError, ReadError, UnsupportedVersion},
+    handlers::{requests, responses, BoxedReqHandler},
+    MessageId, MessageRaw, Response, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
 
+const VERSION_FIELD_LEN: usize = 4;
+
+/// Handles the handshake. Welcome lists all of the supported
+/// messages, so unlike other handlers this one is built
+/// with the ids taken from the dispatcher.
 pub struct Handler;
 
-impl DefaultBuilder<requests::Hello, responses::Welcome> for Handler {
-    fn req_id() -> MessageId {
+impl Handler {
+    pub fn req_id() -> MessageId {
         0
     }
 
-    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Hello, ReadError> {
+    /// Handshake is done only once.
+    pub fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Connected]
+    }
+
+    /// Builds handler answering with the given supported message ids.
+    pub fn build_handler(supported: Vec<MessageId>) -> BoxedReqHandler {
+        Box::new(move |raw: MessageRaw, ctx: &mut connection::Context| {
+            let req = Self::req_from_raw(&raw)?;
+            let resp = Self::handle_request(req, ctx, supported.clone())?;
+            Ok(Box::new(resp) as Box<dyn Response>)
+        })
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Hello, BadRequestError> {
         eprintln!("[{:^15}]: Are you hello?", "HelloHandler");
-        if raw.len() != MSG_HEADER_LEN {
+        if raw.len() < MSG_HEADER_LEN + VERSION_FIELD_LEN {
             eprintln!("[{:^15}]: You are not", "HelloHandler");
-            Err(ReadError::from(format!(
-                "Message len is incorrect. Expected: {}. Actual: {}.",
-                MSG_HEADER_LEN,
+            return Err(ReadError::from(format!(
+                "Message len is incorrect. Expected at least: {}. Actual: {}.",
+                MSG_HEADER_LEN + VERSION_FIELD_LEN,
                 raw.len()
-            )))
-        } else {
-            eprintln!("[{:^15}]: Yes you are", "HelloHandler");
-            Ok(requests::Hello {})
+            ))
+            .into());
         }
+        let mut version: [u8; 4] = [0; 4];
+        version.copy_from_slice(&raw[MSG_HEADER_LEN..MSG_HEADER_LEN + VERSION_FIELD_LEN]);
+        let client_name = String::from_utf8(raw[MSG_HEADER_LEN + VERSION_FIELD_LEN..].to_vec())
+            .map_err(|err| ReadError::from(format!("Client name is not utf-8: {}", err)))?;
+        eprintln!("[{:^15}]: Yes you are", "HelloHandler");
+        Ok(requests::Hello {
+            protocol_version: u32::from_le_bytes(version),
+            client_name,
+        })
     }
 
-    fn handle_request(_req: requests::Hello, ctx: &mut connection::Context) -> Result<responses::Welcome, ReadError> {
-        eprintln!("[{:^15}]: Welcome", "HelloHandler");
-        ctx.initialized = true;
-        Ok(responses::Welcome {})
+    /// Agrees on the newest version both sides speak.
+    /// Clients older than the oldest supported version are rejected.
+    fn handle_request(
+        req: requests::Hello,
+        ctx: &mut connection::Context,
+        supported: Vec<MessageId>,
+    ) -> Result<responses::Welcome, HandlerError> {
+        if req.protocol_version < MIN_PROTOCOL_VERSION {
+            eprintln!(
+                "[{:^15}]: Version {} is too old",
+                "HelloHandler", req.protocol_version
+            );
+            return Err(BadRequestError::from(UnsupportedVersion {
+                client: req.protocol_version,
+                min: MIN_PROTOCOL_VERSION,
+                max: PROTOCOL_VERSION,
+            })
+            .into());
+        }
+        let negotiated_version = req.protocol_version.min(PROTOCOL_VERSION);
+        eprintln!(
+            "[{:^15}]: Welcome {} (protocol {})",
+            "HelloHandler", req.client_name, negotiated_version
+        );
+        ctx.state = connection::State::Greeted;
+        ctx.protocol_version = negotiated_version;
+        ctx.client_name = req.client_name;
+        Ok(responses::Welcome {
+            server_version: PROTOCOL_VERSION,
+            negotiated_version,
+            supported,
+        })
     }
 }
@@ -0,0 +1,46 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{bound_player, lock_error, payload, requests, responses, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::JoinLobby, responses::LobbyJoined> for Handler {
+    fn req_id() -> MessageId {
+        6
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Authenticated]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::JoinLobby, BadRequestError> {
+        let mut reader = payload::Reader::new(raw);
+        let req = requests::JoinLobby {
+            lobby_id: reader.u32()?,
+        };
+        reader.finish()?;
+        Ok(req)
+    }
+
+    fn handle_request(
+        req: requests::JoinLobby,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::LobbyJoined, HandlerError> {
+        let player = bound_player(ctx)?;
+        let seat = shared
+            .lobbies
+            .write()
+            .map_err(lock_error)?
+            .join(req.lobby_id, player)
+            .map_err(BadRequestError::from)?;
+        ctx.state = connection::State::InLobby;
+        Ok(responses::LobbyJoined {
+            lobby_id: req.lobby_id,
+            seat,
+        })
+    }
+}
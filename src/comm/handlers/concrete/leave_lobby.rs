@@ -0,0 +1,39 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{bound_player, lock_error, payload, requests, responses, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::LeaveLobby, responses::LobbyLeft> for Handler {
+    fn req_id() -> MessageId {
+        7
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::InLobby]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::LeaveLobby, BadRequestError> {
+        payload::Reader::new(raw).finish()?;
+        Ok(requests::LeaveLobby {})
+    }
+
+    fn handle_request(
+        _req: requests::LeaveLobby,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::LobbyLeft, HandlerError> {
+        let player = bound_player(ctx)?;
+        let remaining = {
+            let mut lobbies = shared.lobbies.write().map_err(lock_error)?;
+            let id = lobbies.leave(player).map_err(BadRequestError::from)?;
+            lobbies.get(id).map(|lobby| lobby.players()).unwrap_or_default()
+        };
+        ctx.state = connection::State::Authenticated;
+        shared.push_to(&remaining, Box::new(responses::PlayerLeft { player }))?;
+        Ok(responses::LobbyLeft {})
+    }
+}
@@ -0,0 +1,43 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{lock_error, payload, requests, responses, victory_info, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::ListLobbies, responses::LobbyList> for Handler {
+    fn req_id() -> MessageId {
+        5
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Authenticated, connection::State::InLobby]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::ListLobbies, BadRequestError> {
+        payload::Reader::new(raw).finish()?;
+        Ok(requests::ListLobbies {})
+    }
+
+    fn handle_request(
+        _req: requests::ListLobbies,
+        _ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::LobbyList, HandlerError> {
+        let lobbies = shared.lobbies.read().map_err(lock_error)?;
+        let lobbies = lobbies
+            .list_open()
+            .into_iter()
+            .map(|lobby| responses::LobbyInfo {
+                lobby_id: lobby.id,
+                num_of_players: lobby.num_of_players,
+                taken_seats: lobby.seats.len() as u8,
+                board_size: (lobby.board_size.0 as u32, lobby.board_size.1 as u32),
+                victory: victory_info(&lobby.victory),
+            })
+            .collect();
+        Ok(responses::LobbyList { lobbies })
+    }
+}
@@ -0,0 +1,45 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{payload, requests, responses, victory_info, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::ListScenarios, responses::ScenarioList> for Handler {
+    fn req_id() -> MessageId {
+        13
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Authenticated, connection::State::InLobby]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::ListScenarios, BadRequestError> {
+        payload::Reader::new(raw).finish()?;
+        Ok(requests::ListScenarios {})
+    }
+
+    fn handle_request(
+        _req: requests::ListScenarios,
+        _ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::ScenarioList, HandlerError> {
+        let scenarios = shared
+            .scenarios
+            .list()
+            .into_iter()
+            .map(|(name, scenario)| {
+                let (width, height) = scenario.board_size();
+                responses::ScenarioInfo {
+                    name,
+                    num_of_players: scenario.num_of_players,
+                    board_size: (width as u32, height as u32),
+                    victory: victory_info(&scenario.victory),
+                }
+            })
+            .collect();
+        Ok(responses::ScenarioList { scenarios })
+    }
+}
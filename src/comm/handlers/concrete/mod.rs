@@ -1 +1,15 @@
+pub mod attack;
+pub mod auth;
+pub mod battle;
+pub mod create_lobby;
+pub mod create_scenario_lobby;
+pub mod end_turn;
 pub mod hello;
+pub mod join_lobby;
+pub mod leave_lobby;
+pub mod list_lobbies;
+pub mod list_scenarios;
+pub mod move_unit;
+pub mod ping;
+pub mod ready;
+pub mod reconnect;
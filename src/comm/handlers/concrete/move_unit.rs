@@ -0,0 +1,40 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{orders, payload, requests, responses, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::Move, responses::OrderAccepted> for Handler {
+    fn req_id() -> MessageId {
+        9
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::InGame]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Move, BadRequestError> {
+        let mut reader = payload::Reader::new(raw);
+        let req = requests::Move {
+            unit_id: reader.u32()?,
+            position: (reader.u32()?, reader.u32()?),
+        };
+        reader.finish()?;
+        Ok(req)
+    }
+
+    fn handle_request(
+        req: requests::Move,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::OrderAccepted, HandlerError> {
+        let unit_id = req.unit_id as usize;
+        let position = (req.position.0 as usize, req.position.1 as usize);
+        orders::order_unit(shared, ctx, unit_id, |game| {
+            game.move_unit(unit_id, position)
+        })
+    }
+}
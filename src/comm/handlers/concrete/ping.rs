@@ -0,0 +1,38 @@
+use crate::comm::{
+    connection,
+    connection::MSG_HEADER_LEN,
+    errors::{BadRequestError, HandlerError, ReadError},
+    handlers::{requests, responses, DefaultBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl DefaultBuilder<requests::Ping, responses::Pong> for Handler {
+    fn req_id() -> MessageId {
+        1
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        connection::State::ANY
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Ping, BadRequestError> {
+        if raw.len() != MSG_HEADER_LEN {
+            return Err(ReadError::from(format!(
+                "Message len is incorrect. Expected: {}. Actual: {}.",
+                MSG_HEADER_LEN,
+                raw.len()
+            ))
+            .into());
+        }
+        Ok(requests::Ping {})
+    }
+
+    fn handle_request(
+        _req: requests::Ping,
+        _ctx: &mut connection::Context,
+    ) -> Result<responses::Pong, HandlerError> {
+        Ok(responses::Pong {})
+    }
+}
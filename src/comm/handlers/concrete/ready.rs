@@ -0,0 +1,100 @@
+use std::time::Instant;
+
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{
+        bound_player, lock_error, payload, requests, responses, turns, Shared, SharedBuilder,
+    },
+    lobby::Lobby,
+    MessageId, MessageRaw,
+};
+use crate::game::{terrain::Terrain, victory::Victory};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::Ready, responses::ReadySet> for Handler {
+    fn req_id() -> MessageId {
+        8
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::InLobby]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Ready, BadRequestError> {
+        let mut reader = payload::Reader::new(raw);
+        let req = requests::Ready {
+            ready: reader.u8()? != 0,
+        };
+        reader.finish()?;
+        Ok(req)
+    }
+
+    /// Game starts once the last player gets ready.
+    /// Its players are shown the board and the units they see
+    /// before the first turn starts.
+    fn handle_request(
+        req: requests::Ready,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::ReadySet, HandlerError> {
+        let player = bound_player(ctx)?;
+        let (game_started, players, setup, seen) = {
+            let mut lobbies = shared.lobbies.write().map_err(lock_error)?;
+            let started = lobbies
+                .set_ready(player, req.ready, Instant::now())
+                .map_err(BadRequestError::from)?;
+            let lobby = lobbies.lobby_of(player);
+            let players = lobby.map(|lobby| lobby.players());
+            let setup = lobby.and_then(board_setup);
+            let players = players.unwrap_or_default();
+            let seen: Vec<_> = players
+                .iter()
+                .filter_map(|&player| Some((player, turns::units_seen(lobby?, player)?)))
+                .collect();
+            (started, players, setup, seen)
+        };
+        if game_started {
+            eprintln!("[{:^15}]: Everyone is ready, game starts", "LobbyHandler");
+            ctx.state = connection::State::InGame;
+            if let Some(setup) = setup {
+                shared.push_to(&players, Box::new(setup))?;
+            }
+            for (player, units) in seen {
+                shared.push_to(&[player], Box::new(units))?;
+            }
+            turns::start(shared, &players, 0)?;
+        }
+        Ok(responses::ReadySet { game_started })
+    }
+}
+
+/// Describes the board of the lobby's running game.
+fn board_setup(lobby: &Lobby) -> Option<responses::BoardSetup> {
+    let map = lobby.game.as_ref()?.map();
+    let (width, height) = map.size();
+    let mut terrain = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            terrain.push(match map.get((x, y)) {
+                Terrain::Plains => 0,
+                Terrain::Forest => 1,
+                Terrain::Hills => 2,
+                Terrain::Water => 3,
+                Terrain::Wall => 4,
+            });
+        }
+    }
+    let objectives = match &lobby.victory {
+        Victory::Objectives { fields, .. } => {
+            fields.iter().map(|&(x, y)| (x as u32, y as u32)).collect()
+        }
+        _ => Vec::new(),
+    };
+    Some(responses::BoardSetup {
+        board_size: (width as u32, height as u32),
+        terrain,
+        objectives,
+    })
+}
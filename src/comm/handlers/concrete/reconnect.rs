@@ -0,0 +1,169 @@
+use crate::comm::{
+    connection,
+    connection::MSG_HEADER_LEN,
+    errors::{BadRequestError, HandlerError},
+    handlers::{lock_error, requests, responses, turns, Shared, SharedBuilder},
+    MessageId, MessageRaw,
+};
+
+pub struct Handler;
+
+impl SharedBuilder<requests::Reconnect, responses::Resumed> for Handler {
+    fn req_id() -> MessageId {
+        3
+    }
+
+    fn accepted_states() -> &'static [connection::State] {
+        &[connection::State::Greeted]
+    }
+
+    fn req_from_raw(raw: &MessageRaw) -> Result<requests::Reconnect, BadRequestError> {
+        Ok(requests::Reconnect {
+            token: raw[MSG_HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// Binds the connection to the player the token belongs to
+    /// and restores the state the player was in.
+    /// Player back in the game is also told the units it sees now,
+    /// as the oldest of the missed updates could have been dropped.
+    fn handle_request(
+        req: requests::Reconnect,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<responses::Resumed, HandlerError> {
+        let identities = shared.identities.read().map_err(lock_error)?;
+        let identity = identities.verify(&req.token).map_err(BadRequestError::from)?;
+        let (state, mut missed) = shared
+            .sessions
+            .write()
+            .map_err(lock_error)?
+            .resume(identity.player_id, ctx.id)
+            .map_err(BadRequestError::from)?;
+        eprintln!(
+            "[{:^15}]: {} is back, replaying {} updates",
+            "ReconnectHandler",
+            identity.name,
+            missed.len()
+        );
+        let lobbies = shared.lobbies.read().map_err(lock_error)?;
+        let seen = lobbies
+            .lobby_of(identity.player_id)
+            .and_then(|lobby| turns::units_seen(lobby, identity.player_id));
+        if let Some(seen) = seen {
+            missed.push(connection::Handler::response_as_bytes(Box::new(seen)));
+        }
+        ctx.player = Some(identity.player_id);
+        ctx.state = state;
+        Ok(responses::Resumed {
+            player_id: identity.player_id,
+            missed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::comm::errors::{code, error_code};
+    use crate::comm::handlers::{
+        init,
+        tests::{hello, raw_message, test_config},
+        Dispatcher,
+    };
+    use crate::comm::PROTOCOL_VERSION;
+    use crate::game::victory::Victory;
+    use std::time::Instant;
+
+    fn greeted(dispatcher: &Dispatcher, id: usize) -> connection::Context {
+        let mut ctx = connection::Context::new(id);
+        dispatcher
+            .dispatch_from_raw(hello(PROTOCOL_VERSION, ""), &mut ctx)
+            .ok()
+            .unwrap();
+        ctx
+    }
+
+    #[test]
+    fn reconnected_player_gets_its_seat_and_missed_updates() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut old = greeted(&dispatcher, 1);
+        let payload = dispatcher
+            .dispatch_from_raw(raw_message(2, b"alice"), &mut old)
+            .ok()
+            .unwrap()
+            .payload();
+        let token = &payload[4..];
+        old.state = connection::State::InLobby;
+        dispatcher.disconnected(&old, connection::DisconnectReason::TimedOut);
+        let online = dispatcher
+            .shared()
+            .sessions
+            .write()
+            .unwrap()
+            .post(old.player.unwrap(), raw_message(9, &[1]));
+        assert!(online.is_none());
+
+        let mut new = greeted(&dispatcher, 2);
+        let resp = dispatcher
+            .dispatch_from_raw(raw_message(3, token), &mut new)
+            .ok()
+            .unwrap();
+        assert_eq!(resp.id(), 5);
+        assert_eq!(new.player, old.player);
+        assert_eq!(new.state, connection::State::InLobby);
+        let payload = resp.payload();
+        assert_eq!(&payload[4..8], &1u32.to_le_bytes());
+        assert_eq!(&payload[8..], &raw_message(9, &[1])[..]);
+    }
+
+    #[test]
+    fn player_back_in_game_is_told_the_units_it_sees() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut old = greeted(&dispatcher, 1);
+        let payload = dispatcher
+            .dispatch_from_raw(raw_message(2, b"alice"), &mut old)
+            .ok()
+            .unwrap()
+            .payload();
+        let token = &payload[4..];
+        let player = old.player.unwrap();
+        {
+            let mut lobbies = dispatcher.shared().lobbies.write().unwrap();
+            let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+            lobbies.join(id, player).unwrap();
+            lobbies.join(id, player + 1).unwrap();
+            for seated in [player, player + 1] {
+                lobbies.set_ready(seated, true, Instant::now()).unwrap();
+            }
+        }
+        old.state = connection::State::InGame;
+        dispatcher.disconnected(&old, connection::DisconnectReason::TimedOut);
+
+        let mut new = greeted(&dispatcher, 2);
+        let payload = dispatcher
+            .dispatch_from_raw(raw_message(3, token), &mut new)
+            .ok()
+            .unwrap()
+            .payload();
+        assert_eq!(new.state, connection::State::InGame);
+        assert_eq!(&payload[4..8], &1u32.to_le_bytes());
+        let seen = &payload[8..];
+        assert_eq!(&seen[2..6], &20u32.to_le_bytes());
+        // Turn 0 and all of the 6 units of the 10x10 board.
+        assert_eq!(&seen[MSG_HEADER_LEN..MSG_HEADER_LEN + 4], &0u32.to_le_bytes());
+        assert_eq!(&seen[MSG_HEADER_LEN + 4..MSG_HEADER_LEN + 8], &6u32.to_le_bytes());
+    }
+
+    #[test]
+    fn reconnect_with_forged_token_is_rejected() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = greeted(&dispatcher, 1);
+        match dispatcher.dispatch_from_raw(raw_message(3, b"Ym9iOjE="), &mut ctx) {
+            Err(err) => assert_eq!(error_code(&err), code::INVALID_TOKEN),
+            Ok(_) => panic!("forged token should be rejected"),
+        }
+        assert_eq!(ctx.player, None);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::comm::handlers::{concrete, DefaultBuilder, Dispatcher};
+use crate::comm::handlers::{concrete, DefaultBuilder, Dispatcher, Shared, SharedBuilder};
+use crate::config;
 
 macro_rules! register {
     ($f:ident, $h:ident) => {
         if !$f.register(
             concrete::$h::Handler::req_id(),
+            concrete::$h::Handler::accepted_states(),
             concrete::$h::Handler::build_handler(),
         ) {
             panic!(concat!(
@@ -15,12 +17,63 @@ macro_rules! register {
     };
 }
 
+/// Registers handler built with the dispatcher's shared state.
+macro_rules! register_shared {
+    ($f:ident, $h:ident) => {
+        let shared = $f.shared().clone();
+        if !$f.register(
+            concrete::$h::Handler::req_id(),
+            concrete::$h::Handler::accepted_states(),
+            concrete::$h::Handler::build_handler(shared),
+        ) {
+            panic!(concat!(
+                "could not register ",
+                stringify!($h),
+                " builder function"
+            ));
+        }
+    };
+}
+
 /// Returns RequestFactory struct with
 /// all the factory functions registered.
-pub fn new_dispatcher() -> Dispatcher {
-    let mut f = Dispatcher::new();
+pub fn new_dispatcher(config: &config::ServerConfig) -> Dispatcher {
+    let mut f = Dispatcher::new(Shared::from(config));
+
+    register!(f, ping);
+    register_shared!(f, auth);
+    register_shared!(f, reconnect);
+    register_shared!(f, create_lobby);
+    register_shared!(f, list_lobbies);
+    register_shared!(f, join_lobby);
+    register_shared!(f, leave_lobby);
+    register_shared!(f, ready);
+    register_shared!(f, move_unit);
+    register_shared!(f, attack);
+    register_shared!(f, battle);
+    register_shared!(f, end_turn);
+    register_shared!(f, list_scenarios);
+    register_shared!(f, create_scenario_lobby);
+
+    // Hello lists all of the registered messages,
+    // so it has to be registered last.
+    let mut supported = f.message_ids();
+    supported.push(concrete::hello::Handler::req_id());
+    supported.sort_unstable();
+    if !f.register(
+        concrete::hello::Handler::req_id(),
+        concrete::hello::Handler::accepted_states(),
+        concrete::hello::Handler::build_handler(supported),
+    ) {
+        panic!("could not register hello builder function");
+    }
 
-    register!(f, hello);
+    f.register_disconnect(Box::new(|ctx, reason| {
+        eprintln!(
+            "[{:^15}]: Connection {} is gone ({:?}).",
+            "Dispatcher", ctx.id, reason
+        );
+    }));
 
     f
 }
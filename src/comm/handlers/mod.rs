@@ -1,77 +1,294 @@
-use std::collections::HashMap;
-use std::error::Error;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
 
 use crate::comm::{
     connection,
-    connection::{MSG_ID_FIELD_LEN, MSG_SKEY_FIELD_LEN},
-    errors::{BadRequestError, InternalServerError, ReadError},
-    MessageId, MessageRaw, Request, Response,
+    connection::{MSG_HEADER_LEN, MSG_ID_FIELD_LEN, MSG_LEN_FIELD_LEN, MSG_SKEY_FIELD_LEN},
+    errors::{
+        BadRequestError, HandlerError, InternalServerError, InvalidToken, ReadError,
+        UnknownMessageId, WrongState,
+    },
+    identity, lobby, push, scenarios, session, MessageId, MessageRaw, Request, Response,
 };
+use crate::config;
+use crate::game::victory::Victory;
 
 mod concrete;
+mod orders;
+mod payload;
 mod requests;
-mod responses;
+mod turns;
 
 pub mod init;
+pub mod responses;
 
-pub trait ReqHandler: Fn(MessageRaw, &mut connection::Context) -> Option<Box<dyn Response>> {}
-impl<T> ReqHandler for T where T: Fn(MessageRaw, &mut connection::Context) -> Option<Box<dyn Response>> {}
+/// Result of handling a single request.
+pub type HandlerResult = Result<Box<dyn Response>, HandlerError>;
 
-pub type BoxedReqHandler = Box<dyn ReqHandler<Output = Option<Box<dyn Response>>> + Sync + Send>;
+pub trait ReqHandler: Fn(MessageRaw, &mut connection::Context) -> HandlerResult {}
+impl<T> ReqHandler for T where T: Fn(MessageRaw, &mut connection::Context) -> HandlerResult {}
 
-pub trait Builder {
-    fn req_id() -> MessageId;
-    fn build_handler() -> BoxedReqHandler;
-}
+pub type BoxedReqHandler = Box<dyn ReqHandler<Output = HandlerResult> + Sync + Send>;
+
+/// Called once the connection is closed with its last context.
+pub type BoxedDisconnectHandler =
+    Box<dyn Fn(&connection::Context, connection::DisconnectReason) + Sync + Send>;
 
-// todo Box<Error> ?
 pub trait DefaultBuilder<T: Request, U: Response + 'static> {
     fn req_id() -> MessageId;
 
-    fn req_from_raw(raw: &MessageRaw) -> Result<T, ReadError>;
-    fn handle_request(req: T, ctx: &mut connection::Context) -> Result<U, ReadError>;
+    /// Connection states in which the request can be handled.
+    fn accepted_states() -> &'static [connection::State];
+
+    /// Parses request from the raw message.
+    /// Failing to do so is always the client's fault.
+    fn req_from_raw(raw: &MessageRaw) -> Result<T, BadRequestError>;
+    fn handle_request(req: T, ctx: &mut connection::Context) -> Result<U, HandlerError>;
 
     fn build_handler() -> BoxedReqHandler {
         Box::new(|raw: MessageRaw, ctx: &mut connection::Context| {
-            let req = match Self::req_from_raw(&raw) {
-                Err(_) => return None,
-                Ok(val) => val,
-            };
-            match Self::handle_request(req, ctx) {
-                Ok(resp) => Some(Box::new(resp)),
-                Err(_) => None,
+            let req = Self::req_from_raw(&raw)?;
+            let resp = Self::handle_request(req, ctx)?;
+            Ok(Box::new(resp) as Box<dyn Response>)
+        })
+    }
+}
+
+/// State shared by the handlers of all of the connections.
+/// Locks are always taken in the order of the fields.
+#[derive(Clone)]
+pub struct Shared {
+    pub identities: Arc<RwLock<identity::Registry>>,
+    pub sessions: Arc<RwLock<session::Sessions>>,
+    pub lobbies: Arc<RwLock<lobby::LobbyManager>>,
+    pub pushes: Arc<RwLock<push::PushQueues>>,
+    /// Scenarios lobbies can be created from. Never changes, so it is not locked.
+    pub scenarios: Arc<scenarios::Catalog>,
+}
+
+impl Shared {
+    /// Updates state of the authenticated connection.
+    /// Lobby manager tells whether the player sits in a lobby
+    /// or plays, as the game is started by the other player's request.
+    pub fn refresh_state(&self, ctx: &mut connection::Context) -> Result<(), HandlerError> {
+        let player = match ctx.player {
+            Some(val) if ctx.state.is_authenticated() => val,
+            _ => return Ok(()),
+        };
+        let lobbies = self.lobbies.read().map_err(lock_error)?;
+        ctx.state = match lobbies.lobby_of(player) {
+            Some(lobby) if lobby.game.is_some() => connection::State::InGame,
+            Some(_) => connection::State::InLobby,
+            None => connection::State::Authenticated,
+        };
+        Ok(())
+    }
+
+    /// Pushes the update to the connections of the online players.
+    /// Offline players get it once they reconnect.
+    /// Lobbies can't be locked by the caller.
+    pub fn push_to(
+        &self,
+        players: &[identity::PlayerId],
+        update: Box<dyn Response>,
+    ) -> Result<(), HandlerError> {
+        let frame = connection::Handler::response_as_bytes(update);
+        let mut sessions = self.sessions.write().map_err(lock_error)?;
+        let pushes = self.pushes.read().map_err(lock_error)?;
+        for &player in players.iter() {
+            if let Some(conn_id) = sessions.post(player, frame.clone()) {
+                if !pushes.push(conn_id, frame.clone()) {
+                    sessions.keep_missed(player, frame.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks player of the closed connection as offline
+    /// and closes the connection's push queue. Updates the connection
+    /// didn't get to send are kept for the player to resume.
+    fn detach(&self, ctx: &connection::Context) -> Result<(), HandlerError> {
+        let mut sessions = self.sessions.write().map_err(lock_error)?;
+        sessions.detach(ctx);
+        let unsent = self.pushes.write().map_err(lock_error)?.close(ctx.id);
+        if let Some(player) = ctx.player {
+            for frame in unsent {
+                sessions.keep_missed(player, frame);
             }
+        }
+        Ok(())
+    }
+}
+
+impl From<&config::ServerConfig> for Shared {
+    fn from(config: &config::ServerConfig) -> Self {
+        Shared {
+            identities: Arc::new(RwLock::new(identity::Registry::default())),
+            sessions: Arc::new(RwLock::new(session::Sessions::new(
+                config.session_backlog_len,
+            ))),
+            lobbies: Arc::new(RwLock::new(lobby::LobbyManager::new(Duration::from_millis(
+                config.turn_duration_ms,
+            )))),
+            pushes: Arc::new(RwLock::new(push::PushQueues::default())),
+            scenarios: Arc::new(scenarios::Catalog::new(&config.scenario_dir)),
+        }
+    }
+}
+
+/// Like DefaultBuilder but for the handlers
+/// that need the state shared between the connections.
+pub trait SharedBuilder<T: Request, U: Response + 'static> {
+    fn req_id() -> MessageId;
+
+    /// Connection states in which the request can be handled.
+    fn accepted_states() -> &'static [connection::State];
+
+    /// Parses request from the raw message.
+    /// Failing to do so is always the client's fault.
+    fn req_from_raw(raw: &MessageRaw) -> Result<T, BadRequestError>;
+    fn handle_request(
+        req: T,
+        ctx: &mut connection::Context,
+        shared: &Shared,
+    ) -> Result<U, HandlerError>;
+
+    fn build_handler(shared: Shared) -> BoxedReqHandler {
+        Box::new(move |raw: MessageRaw, ctx: &mut connection::Context| {
+            let req = Self::req_from_raw(&raw)?;
+            let resp = Self::handle_request(req, ctx, &shared)?;
+            Ok(Box::new(resp) as Box<dyn Response>)
         })
     }
 }
 
+/// Returns player the connection acts for.
+pub fn bound_player(ctx: &connection::Context) -> Result<identity::PlayerId, HandlerError> {
+    ctx.player
+        .ok_or_else(|| BadRequestError::from(InvalidToken).into())
+}
+
+/// Turns poisoned lock into the error response.
+pub fn lock_error<T>(err: T) -> HandlerError
+where
+    T: ToString,
+{
+    InternalServerError(Box::from(err.to_string())).into()
+}
+
+/// Encodes the victory condition as its kind
+/// and the number of turns it depends on.
+pub fn victory_info(victory: &Victory) -> (u8, u32) {
+    match *victory {
+        Victory::LastStanding => (0, 0),
+        Victory::Objectives { turns, .. } => (1, turns),
+        Victory::TurnLimit(turns) => (2, turns),
+    }
+}
+
+/// Request handler along with the connection states it accepts.
+struct Registered {
+    accepted_states: &'static [connection::State],
+    handler: BoxedReqHandler,
+}
+
+impl Registered {
+    /// Requests accepted only after the authentication affect the game,
+    /// so they have to carry the player token.
+    fn requires_token(&self) -> bool {
+        self.accepted_states.iter().all(|state| state.is_authenticated())
+    }
+}
+
 pub struct Dispatcher {
-    handlers: HashMap<MessageId, BoxedReqHandler>,
+    handlers: HashMap<MessageId, Registered>,
+    disconnect_handlers: Vec<BoxedDisconnectHandler>,
+    shared: Shared,
 }
 
 impl Dispatcher {
-    pub fn new() -> Dispatcher {
+    pub fn new(shared: Shared) -> Dispatcher {
         Dispatcher {
             handlers: HashMap::new(),
+            disconnect_handlers: Vec::new(),
+            shared,
         }
     }
 
-    pub fn dispatch_from_raw(&self, raw: MessageRaw, ctx: &mut connection::Context) -> Result<Box<dyn Response>, Box<dyn Error>> {
+    /// Returns state shared by the handlers.
+    pub fn shared(&self) -> &Shared {
+        &self.shared
+    }
+
+    /// Passes the message to its handler.
+    /// Requests not accepted in the current connection state
+    /// are rejected without being parsed.
+    /// Game affecting requests have their token verified
+    /// and stripped before they are passed on.
+    pub fn dispatch_from_raw(&self, raw: MessageRaw, ctx: &mut connection::Context) -> HandlerResult {
         let id = Self::read_id(&raw);
+        self.shared.refresh_state(ctx)?;
         match self.handlers.get(&id) {
-            None => Err(Box::new(BadRequestError::from(ReadError::from(format!(
-                "Mess id ({}) doesn't match any of registered ones.",
-                id
-            ))))),
-            Some(handler) => {
-                return match handler(raw, ctx) {
-                    None => Err(Box::new(InternalServerError(Box::new(ReadError::from(
-                        format!("Req handler for message with id {} returned None", id),
-                    ))))),
-                    Some(resp) => Ok(resp),
-                };
+            None => Err(BadRequestError::from(UnknownMessageId { id }).into()),
+            Some(registered) if !registered.accepted_states.contains(&ctx.state) => {
+                Err(BadRequestError::from(WrongState {
+                    id,
+                    state: ctx.state,
+                })
+                .into())
+            }
+            Some(registered) if registered.requires_token() => {
+                let raw = self.authorize(raw, ctx)?;
+                (registered.handler)(raw, ctx)
             }
+            Some(registered) => (registered.handler)(raw, ctx),
+        }
+    }
+
+    /// Checks that the token prefixing the payload belongs to
+    /// the player bound to the connection and that the connection
+    /// still acts for the player.
+    /// Returns the message without the token.
+    ///
+    /// Token is prefixed as | token len | token |
+    /// with the length being u32 little endian.
+    fn authorize(&self, raw: MessageRaw, ctx: &connection::Context) -> Result<MessageRaw, HandlerError> {
+        let token_start = MSG_HEADER_LEN + MSG_LEN_FIELD_LEN;
+        if raw.len() < token_start {
+            return Err(BadRequestError::from(ReadError::from(String::from(
+                "Missing player token",
+            )))
+            .into());
+        }
+        let mut token_len: [u8; 4] = [0; 4];
+        token_len.copy_from_slice(&raw[MSG_HEADER_LEN..token_start]);
+        let token_end = token_start.saturating_add(u32::from_le_bytes(token_len) as usize);
+        if raw.len() < token_end {
+            return Err(BadRequestError::from(ReadError::from(String::from(
+                "Player token longer than the message",
+            )))
+            .into());
         }
+        let identities = self.shared.identities.read().map_err(lock_error)?;
+        let identity = identities
+            .verify(&raw[token_start..token_end])
+            .map_err(BadRequestError::from)?;
+        let sessions = self.shared.sessions.read().map_err(lock_error)?;
+        if ctx.player != Some(identity.player_id)
+            || !sessions.is_attached(identity.player_id, ctx.id)
+        {
+            return Err(BadRequestError::from(InvalidToken).into());
+        }
+        let payload = &raw[token_end..];
+        let mut stripped = Vec::with_capacity(MSG_HEADER_LEN + payload.len());
+        stripped.extend_from_slice(&raw[..MSG_SKEY_FIELD_LEN + MSG_ID_FIELD_LEN]);
+        stripped.extend(&(payload.len() as u32).to_le_bytes());
+        stripped.extend_from_slice(payload);
+        Ok(stripped)
     }
 
     fn read_id(raw: &MessageRaw) -> MessageId {
@@ -80,12 +297,363 @@ impl Dispatcher {
         u32::from_le_bytes(id_bytes)
     }
 
-    pub fn register(&mut self, id: MessageId, builder: BoxedReqHandler) -> bool {
-        if self.handlers.contains_key(&id) {
-            false
-        } else {
-            self.handlers.insert(id, builder);
-            true
+    /// Registers handler of the request with the given id,
+    /// accepted only in the given connection states.
+    /// Returns false if the id is already taken.
+    pub fn register(
+        &mut self,
+        id: MessageId,
+        accepted_states: &'static [connection::State],
+        builder: BoxedReqHandler,
+    ) -> bool {
+        match self.handlers.entry(id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(Registered {
+                    accepted_states,
+                    handler: builder,
+                });
+                true
+            }
         }
     }
+
+    /// Returns ids of all of the registered requests.
+    pub fn message_ids(&self) -> Vec<MessageId> {
+        let mut ids: Vec<MessageId> = self.handlers.keys().cloned().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Resolves turns whose planning time ran out.
+    /// Called periodically by the server.
+    pub fn tick(&self, now: Instant) {
+        if let Err(err) = turns::resolve_due(&self.shared, now) {
+            eprintln!("[{:^15}]: Could not resolve turns! {}", "Dispatcher", err);
+        }
+    }
+
+    /// Registers function run for every closed connection.
+    pub fn register_disconnect(&mut self, handler: BoxedDisconnectHandler) {
+        self.disconnect_handlers.push(handler);
+    }
+
+    /// Opens queue of the messages pushed to the new connection.
+    pub fn connected(&self, ctx: &connection::Context) -> push::PushQueue {
+        match self.shared.pushes.write() {
+            Ok(mut pushes) => pushes.open(ctx.id),
+            Err(err) => {
+                eprintln!("[{:^15}]: Could not open push queue! {}", "Dispatcher", err);
+                push::PushQueue::default()
+            }
+        }
+    }
+
+    /// Detaches the player of the closed connection, so it can reconnect,
+    /// and reports the connection to all of the disconnect handlers.
+    pub fn disconnected(&self, ctx: &connection::Context, reason: connection::DisconnectReason) {
+        if let Err(err) = self.shared.detach(ctx) {
+            eprintln!("[{:^15}]: Could not detach the session! {}", "Dispatcher", err);
+        }
+        for handler in self.disconnect_handlers.iter() {
+            handler(ctx, reason);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::comm::connection::SKEY;
+    use crate::comm::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+    use crate::comm::errors::{code, error_code};
+
+    pub fn test_config() -> config::ServerConfig {
+        config::ServerConfig::new(String::from("127.0.0.1"), 0)
+    }
+
+    pub fn raw_message(id: MessageId, payload: &[u8]) -> MessageRaw {
+        let mut raw = SKEY.to_vec();
+        raw.extend(&id.to_le_bytes());
+        raw.extend(&(payload.len() as u32).to_le_bytes());
+        raw.extend_from_slice(payload);
+        raw
+    }
+
+    pub fn hello(version: u32, name: &str) -> MessageRaw {
+        let mut payload = version.to_le_bytes().to_vec();
+        payload.extend_from_slice(name.as_bytes());
+        raw_message(0, &payload)
+    }
+
+    #[test]
+    fn unknown_message_is_a_bad_request() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        match dispatcher.dispatch_from_raw(raw_message(1234, &[]), &mut ctx) {
+            Err(err) => {
+                assert!(err.is_client_error());
+                assert_eq!(error_code(&err), code::UNKNOWN_MESSAGE);
+            }
+            Ok(_) => panic!("unknown message should not be handled"),
+        }
+    }
+
+    #[test]
+    fn malformed_hello_is_a_bad_request() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        match dispatcher.dispatch_from_raw(raw_message(0, &[1, 2, 3]), &mut ctx) {
+            Err(err) => {
+                assert!(err.is_client_error());
+                assert_eq!(error_code(&err), code::READ_ERROR);
+            }
+            Ok(_) => panic!("malformed hello should not be handled"),
+        }
+        assert_eq!(ctx.state, connection::State::Connected);
+    }
+
+    #[test]
+    fn hello_is_welcomed() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        let resp = dispatcher.dispatch_from_raw(hello(PROTOCOL_VERSION, "tester"), &mut ctx);
+        assert_eq!(resp.ok().unwrap().id(), 1);
+        assert_eq!(ctx.state, connection::State::Greeted);
+        assert_eq!(ctx.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(ctx.client_name, "tester");
+    }
+
+    #[test]
+    fn welcome_lists_registered_messages() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        let resp = dispatcher.dispatch_from_raw(hello(PROTOCOL_VERSION, ""), &mut ctx);
+        let payload = resp.ok().unwrap().payload();
+        let ids = dispatcher.message_ids();
+        assert_eq!(&payload[8..12], &(ids.len() as u32).to_le_bytes());
+        let listed: Vec<MessageId> = payload[12..]
+            .chunks(4)
+            .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+            .collect();
+        assert_eq!(listed, ids);
+    }
+
+    #[test]
+    fn newer_client_is_downgraded() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        let resp = dispatcher.dispatch_from_raw(hello(PROTOCOL_VERSION + 1, "future"), &mut ctx);
+        let payload = resp.ok().unwrap().payload();
+        assert_eq!(&payload[..4], &PROTOCOL_VERSION.to_le_bytes());
+        assert_eq!(&payload[4..8], &PROTOCOL_VERSION.to_le_bytes());
+        assert_eq!(ctx.protocol_version, PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn too_old_client_is_rejected() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        match dispatcher.dispatch_from_raw(hello(MIN_PROTOCOL_VERSION - 1, "past"), &mut ctx) {
+            Err(err) => assert_eq!(error_code(&err), code::UNSUPPORTED_VERSION),
+            Ok(_) => panic!("unsupported version should be rejected"),
+        }
+        assert_eq!(ctx.state, connection::State::Connected);
+    }
+
+    #[test]
+    fn ping_is_ponged() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        let resp = dispatcher.dispatch_from_raw(raw_message(1, &[]), &mut ctx);
+        assert_eq!(resp.ok().unwrap().id(), 3);
+    }
+
+    #[test]
+    fn disconnection_is_reported() {
+        use std::sync::{Arc, Mutex};
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let mut dispatcher = Dispatcher::new(Shared::from(&test_config()));
+        let sink = reported.clone();
+        dispatcher.register_disconnect(Box::new(move |ctx, reason| {
+            sink.lock().unwrap().push((ctx.id, reason));
+        }));
+        dispatcher.disconnected(
+            &connection::Context::new(7),
+            connection::DisconnectReason::TimedOut,
+        );
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![(7, connection::DisconnectReason::TimedOut)]
+        );
+    }
+
+    #[test]
+    fn hello_is_accepted_only_once() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        assert!(dispatcher
+            .dispatch_from_raw(hello(PROTOCOL_VERSION, ""), &mut ctx)
+            .is_ok());
+        match dispatcher.dispatch_from_raw(hello(PROTOCOL_VERSION, ""), &mut ctx) {
+            Err(err) => {
+                assert!(err.is_client_error());
+                assert_eq!(error_code(&err), code::WRONG_STATE);
+            }
+            Ok(_) => panic!("second hello should be rejected"),
+        }
+    }
+
+    #[test]
+    fn request_outside_accepted_states_is_not_handled() {
+        let mut dispatcher = Dispatcher::new(Shared::from(&test_config()));
+        dispatcher.register(
+            7,
+            &[connection::State::InGame],
+            Box::new(|_, _| panic!("handler should not be called")),
+        );
+        let mut ctx = connection::Context::new(0);
+        ctx.state = connection::State::InLobby;
+        match dispatcher.dispatch_from_raw(raw_message(7, &[]), &mut ctx) {
+            Err(err) => assert_eq!(error_code(&err), code::WRONG_STATE),
+            Ok(_) => panic!("request should be rejected"),
+        }
+    }
+
+    fn with_token(id: MessageId, token: &str, payload: &[u8]) -> MessageRaw {
+        let mut full = (token.len() as u32).to_le_bytes().to_vec();
+        full.extend_from_slice(token.as_bytes());
+        full.extend_from_slice(payload);
+        raw_message(id, &full)
+    }
+
+    fn echo_dispatcher() -> Dispatcher {
+        let mut dispatcher = Dispatcher::new(Shared::from(&test_config()));
+        dispatcher.register(
+            7,
+            &[connection::State::Authenticated],
+            Box::new(|raw, _| {
+                assert_eq!(raw, raw_message(7, &[1, 2, 3]));
+                Ok(Box::new(responses::Goodbye {}) as Box<dyn Response>)
+            }),
+        );
+        dispatcher
+    }
+
+    fn authenticated(dispatcher: &Dispatcher, name: &str) -> (connection::Context, String) {
+        let identity = dispatcher
+            .shared()
+            .identities
+            .write()
+            .unwrap()
+            .register(String::from(name))
+            .unwrap();
+        let mut ctx = connection::Context::new(identity.player_id as usize);
+        ctx.state = connection::State::Authenticated;
+        ctx.player = Some(identity.player_id);
+        dispatcher
+            .shared()
+            .sessions
+            .write()
+            .unwrap()
+            .open(identity.player_id, &ctx);
+        (ctx, identity.token())
+    }
+
+    #[test]
+    fn auth_binds_connection_to_player() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        dispatcher
+            .dispatch_from_raw(hello(PROTOCOL_VERSION, ""), &mut ctx)
+            .ok()
+            .unwrap();
+        let resp = dispatcher.dispatch_from_raw(raw_message(2, b"alice"), &mut ctx);
+        let payload = resp.ok().unwrap().payload();
+        assert_eq!(ctx.state, connection::State::Authenticated);
+        let player = ctx.player.unwrap();
+        assert_eq!(&payload[..4], &player.to_le_bytes());
+        let identities = dispatcher.shared().identities.read().unwrap();
+        assert_eq!(identities.verify(&payload[4..]).unwrap().player_id, player);
+    }
+
+    #[test]
+    fn auth_requires_handshake() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let mut ctx = connection::Context::new(0);
+        match dispatcher.dispatch_from_raw(raw_message(2, b"alice"), &mut ctx) {
+            Err(err) => assert_eq!(error_code(&err), code::WRONG_STATE),
+            Ok(_) => panic!("auth before hello should be rejected"),
+        }
+    }
+
+    #[test]
+    fn game_request_with_valid_token_is_handled_without_it() {
+        let dispatcher = echo_dispatcher();
+        let (mut ctx, token) = authenticated(&dispatcher, "alice");
+        let resp = dispatcher.dispatch_from_raw(with_token(7, &token, &[1, 2, 3]), &mut ctx);
+        assert!(resp.is_ok());
+    }
+
+    #[test]
+    fn game_request_without_token_is_rejected() {
+        let dispatcher = echo_dispatcher();
+        let (mut ctx, _) = authenticated(&dispatcher, "alice");
+        match dispatcher.dispatch_from_raw(raw_message(7, &[1, 2, 3]), &mut ctx) {
+            Err(err) => assert!(err.is_client_error()),
+            Ok(_) => panic!("request without token should be rejected"),
+        }
+    }
+
+    #[test]
+    fn game_request_with_someone_elses_token_is_rejected() {
+        let dispatcher = echo_dispatcher();
+        let (mut ctx, _) = authenticated(&dispatcher, "alice");
+        let (_, bobs_token) = authenticated(&dispatcher, "bob");
+        match dispatcher.dispatch_from_raw(with_token(7, &bobs_token, &[1, 2, 3]), &mut ctx) {
+            Err(err) => assert_eq!(error_code(&err), code::INVALID_TOKEN),
+            Ok(_) => panic!("request with other player's token should be rejected"),
+        }
+    }
+
+    fn turn_started(turn: u32) -> Box<dyn Response> {
+        Box::new(responses::TurnStarted {
+            turn,
+            duration_ms: 100,
+        })
+    }
+
+    #[test]
+    fn unsent_pushes_are_kept_for_the_disconnected_player() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let (ctx, _) = authenticated(&dispatcher, "alice");
+        let queue = dispatcher.connected(&ctx);
+        let player = ctx.player.unwrap();
+        let shared = dispatcher.shared();
+        shared.push_to(&[player], turn_started(1)).unwrap();
+        dispatcher.disconnected(&ctx, connection::DisconnectReason::TimedOut);
+        shared.push_to(&[player], turn_started(2)).unwrap();
+        assert!(queue.take().is_empty());
+
+        let (_, missed) = shared.sessions.write().unwrap().resume(player, 2).unwrap();
+        let turns: Vec<&[u8]> = missed.iter().map(|frame| &frame[10..14]).collect();
+        assert_eq!(turns, vec![&1u32.to_le_bytes(), &2u32.to_le_bytes()]);
+    }
+
+    #[test]
+    fn push_to_closed_queue_is_kept_for_the_player() {
+        let dispatcher = init::new_dispatcher(&test_config());
+        let (ctx, _) = authenticated(&dispatcher, "alice");
+        let queue = dispatcher.connected(&ctx);
+        let player = ctx.player.unwrap();
+        let shared = dispatcher.shared();
+        shared.pushes.write().unwrap().close(ctx.id);
+        shared.push_to(&[player], turn_started(1)).unwrap();
+        assert!(queue.take().is_empty());
+
+        let (_, missed) = shared.sessions.write().unwrap().resume(player, 2).unwrap();
+        assert_eq!(missed.len(), 1);
+    }
 }
@@ -0,0 +1,100 @@
+use crate::comm::{
+    connection,
+    errors::{BadRequestError, HandlerError},
+    handlers::{bound_player, lock_error, responses, Shared},
+};
+use crate::game::{error::GameError, Game};
+
+/// Gives order to the unit of the player the connection acts for.
+/// Units of the other players are rejected as non existing,
+/// so the order doesn't tell which of them are in the game.
+/// Game errors are returned to the client as bad requests.
+pub fn order_unit<F>(
+    shared: &Shared,
+    ctx: &connection::Context,
+    unit_id: usize,
+    order: F,
+) -> Result<responses::OrderAccepted, HandlerError>
+where
+    F: FnOnce(&mut Game) -> Result<(), GameError>,
+{
+    let player = bound_player(ctx)?;
+    let mut lobbies = shared.lobbies.write().map_err(lock_error)?;
+    let (game, seat) = lobbies.game_of_mut(player).map_err(BadRequestError::from)?;
+    if !game.get_unit(unit_id).is_ok_and(|unit| unit.owner_id == seat) {
+        return Err(BadRequestError::from(GameError::NonExistingUnit(unit_id)).into());
+    }
+    order(game).map_err(BadRequestError::from)?;
+    Ok(responses::OrderAccepted {})
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::time::Instant;
+    use crate::comm::errors::{code, error_code};
+    use crate::comm::handlers::tests::test_config;
+    use crate::game::unit::State;
+    use crate::game::victory::Victory;
+
+    /// Returns shared state with the running game of players 0 and 1
+    /// and context of the player 0.
+    pub fn running_game() -> (Shared, connection::Context) {
+        let shared = Shared::from(&test_config());
+        {
+            let mut lobbies = shared.lobbies.write().unwrap();
+            let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+            for player in 0..2 {
+                lobbies.join(id, player).unwrap();
+                lobbies.set_ready(player, true, Instant::now()).unwrap();
+            }
+        }
+        let mut ctx = connection::Context::new(0);
+        ctx.player = Some(0);
+        ctx.state = connection::State::InGame;
+        (shared, ctx)
+    }
+
+    pub fn unit_state(shared: &Shared, unit_id: usize) -> State {
+        let mut lobbies = shared.lobbies.write().unwrap();
+        lobbies.game_of_mut(0).unwrap().0.get_unit(unit_id).unwrap().state
+    }
+
+    #[test]
+    fn own_unit_is_ordered() {
+        let (shared, ctx) = running_game();
+        order_unit(&shared, &ctx, 0, |game| game.move_unit(0, (0, 3))).unwrap();
+        match unit_state(&shared, 0) {
+            State::Moving(0, 3) => (),
+            state => panic!("unit should be moving, is {:?}", state),
+        }
+    }
+
+    #[test]
+    fn other_players_unit_is_not_ordered() {
+        let (shared, ctx) = running_game();
+        let err = order_unit(&shared, &ctx, 3, |game| game.move_unit(3, (0, 5)))
+            .err()
+            .unwrap();
+        assert_eq!(error_code(&err), code::NON_EXISTING_UNIT);
+        match unit_state(&shared, 3) {
+            State::Idle => (),
+            state => panic!("unit should stay idle, is {:?}", state),
+        }
+    }
+
+    #[test]
+    fn game_errors_are_bad_requests() {
+        let (shared, ctx) = running_game();
+        let err = order_unit(&shared, &ctx, 0, |game| game.move_unit(0, (20, 20)))
+            .err()
+            .unwrap();
+        assert!(err.is_client_error());
+        assert_eq!(error_code(&err), code::POSITION_OUTSIDE_THE_BOARD);
+        let err = order_unit(&shared, &ctx, 42, |game| game.move_unit(42, (1, 1)))
+            .err()
+            .unwrap();
+        assert_eq!(error_code(&err), code::NON_EXISTING_UNIT);
+    }
+}
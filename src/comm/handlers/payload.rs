@@ -0,0 +1,96 @@
+use crate::comm::{
+    connection::MSG_HEADER_LEN,
+    errors::{BadRequestError, ReadError},
+    MessageRaw,
+};
+
+/// Reads little endian fields of the request payload one after another.
+pub struct Reader<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Creates reader of the message's payload, header is skipped.
+    pub fn new(raw: &'a MessageRaw) -> Reader<'a> {
+        Reader {
+            payload: &raw[MSG_HEADER_LEN.min(raw.len())..],
+        }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, BadRequestError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, BadRequestError> {
+        let mut bytes: [u8; 4] = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, BadRequestError> {
+        let mut bytes: [u8; 8] = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Checks if there are fields left to read.
+    /// Used for the optional trailing fields.
+    pub fn has_more(&self) -> bool {
+        !self.payload.is_empty()
+    }
+
+    /// Checks that the whole payload was read.
+    pub fn finish(self) -> Result<(), BadRequestError> {
+        if !self.payload.is_empty() {
+            return Err(ReadError::from(format!(
+                "Payload has {} unexpected trailing bytes.",
+                self.payload.len()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BadRequestError> {
+        if self.payload.len() < len {
+            return Err(ReadError::from(String::from("Payload is too short.")).into());
+        }
+        let (field, rest) = self.payload.split_at(len);
+        self.payload = rest;
+        Ok(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::comm::handlers::tests::raw_message;
+
+    #[test]
+    fn fields_are_read_in_order() {
+        let mut payload = vec![7];
+        payload.extend(&1234u32.to_le_bytes());
+        payload.extend(&5678u64.to_le_bytes());
+        let raw = raw_message(1, &payload);
+        let mut reader = Reader::new(&raw);
+        assert_eq!(reader.u8().unwrap(), 7);
+        assert_eq!(reader.u32().unwrap(), 1234);
+        assert_eq!(reader.u64().unwrap(), 5678);
+        assert!(reader.finish().is_ok());
+    }
+
+    #[test]
+    fn too_short_payload_is_rejected() {
+        let raw = raw_message(1, &[1, 2]);
+        assert!(Reader::new(&raw).u32().is_err());
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let raw = raw_message(1, &[1, 2]);
+        let mut reader = Reader::new(&raw);
+        reader.u8().unwrap();
+        assert!(reader.finish().is_err());
+    }
+}
@@ -1,13 +1,246 @@
 use crate::comm::{Message, MessageId, Payload};
 
-pub struct Hello;
+/// Opens the handshake.
+/// Carries newest protocol version the client speaks and its name.
+pub struct Hello {
+    pub protocol_version: u32,
+    pub client_name: String,
+}
 
 impl Message for Hello {
     fn id(&self) -> MessageId {
         0
     }
 
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(4 + self.client_name.len());
+        payload.extend(&self.protocol_version.to_le_bytes());
+        payload.extend_from_slice(self.client_name.as_bytes());
+        payload
+    }
+}
+
+/// Heartbeat. Keeps the connection from timing out.
+pub struct Ping;
+
+impl Message for Ping {
+    fn id(&self) -> MessageId {
+        1
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+/// Registers the player under the given name.
+pub struct Auth {
+    pub name: String,
+}
+
+impl Message for Auth {
+    fn id(&self) -> MessageId {
+        2
+    }
+
+    fn payload(&self) -> Payload {
+        self.name.as_bytes().to_vec()
+    }
+}
+
+/// Resumes the session of the player the token belongs to.
+pub struct Reconnect {
+    pub token: Vec<u8>,
+}
+
+impl Message for Reconnect {
+    fn id(&self) -> MessageId {
+        3
+    }
+
+    fn payload(&self) -> Payload {
+        self.token.clone()
+    }
+}
+
+/// Opens new lobby and seats the sender in it.
+pub struct CreateLobby {
+    pub num_of_players: u8,
+    pub board_size: (u32, u32),
+    /// Victory condition of the game, see the protocol description.
+    pub victory: u8,
+    /// Number of turns the victory condition depends on.
+    pub victory_turns: u32,
+    /// Seed of the generated map. Board is all plains without it.
+    pub map_seed: Option<u64>,
+}
+
+impl Message for CreateLobby {
+    fn id(&self) -> MessageId {
+        4
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = vec![self.num_of_players];
+        payload.extend(&self.board_size.0.to_le_bytes());
+        payload.extend(&self.board_size.1.to_le_bytes());
+        payload.push(self.victory);
+        payload.extend(&self.victory_turns.to_le_bytes());
+        if let Some(seed) = self.map_seed {
+            payload.extend(&seed.to_le_bytes());
+        }
+        payload
+    }
+}
+
+/// Asks for the lobbies that can be joined.
+pub struct ListLobbies;
+
+impl Message for ListLobbies {
+    fn id(&self) -> MessageId {
+        5
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+pub struct JoinLobby {
+    pub lobby_id: u32,
+}
+
+impl Message for JoinLobby {
+    fn id(&self) -> MessageId {
+        6
+    }
+
+    fn payload(&self) -> Payload {
+        self.lobby_id.to_le_bytes().to_vec()
+    }
+}
+
+pub struct LeaveLobby;
+
+impl Message for LeaveLobby {
+    fn id(&self) -> MessageId {
+        7
+    }
+
     fn payload(&self) -> Payload {
         Vec::new()
     }
 }
+
+/// Marks the sender as ready to play or not.
+pub struct Ready {
+    pub ready: bool,
+}
+
+impl Message for Ready {
+    fn id(&self) -> MessageId {
+        8
+    }
+
+    fn payload(&self) -> Payload {
+        vec![self.ready as u8]
+    }
+}
+
+/// Orders the unit to move to the given position.
+pub struct Move {
+    pub unit_id: u32,
+    pub position: (u32, u32),
+}
+
+impl Message for Move {
+    fn id(&self) -> MessageId {
+        9
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = self.unit_id.to_le_bytes().to_vec();
+        payload.extend(&self.position.0.to_le_bytes());
+        payload.extend(&self.position.1.to_le_bytes());
+        payload
+    }
+}
+
+/// Orders the unit to attack the given position.
+pub struct Attack {
+    pub unit_id: u32,
+    pub position: (u32, u32),
+}
+
+impl Message for Attack {
+    fn id(&self) -> MessageId {
+        10
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = self.unit_id.to_le_bytes().to_vec();
+        payload.extend(&self.position.0.to_le_bytes());
+        payload.extend(&self.position.1.to_le_bytes());
+        payload
+    }
+}
+
+/// Orders the unit to battle the other one.
+pub struct Battle {
+    pub unit_id: u32,
+    pub target_id: u32,
+}
+
+impl Message for Battle {
+    fn id(&self) -> MessageId {
+        11
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = self.unit_id.to_le_bytes().to_vec();
+        payload.extend(&self.target_id.to_le_bytes());
+        payload
+    }
+}
+
+/// Tells that the player is done with giving orders this turn.
+pub struct EndTurn;
+
+impl Message for EndTurn {
+    fn id(&self) -> MessageId {
+        12
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+/// Asks for the scenarios lobbies can be created from.
+pub struct ListScenarios;
+
+impl Message for ListScenarios {
+    fn id(&self) -> MessageId {
+        13
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+/// Opens new lobby playing the scenario with the given name
+/// and seats the sender in it.
+pub struct CreateScenarioLobby {
+    pub name: String,
+}
+
+impl Message for CreateScenarioLobby {
+    fn id(&self) -> MessageId {
+        14
+    }
+
+    fn payload(&self) -> Payload {
+        self.name.as_bytes().to_vec()
+    }
+}
@@ -1,13 +1,558 @@
-use crate::comm::{Message, MessageId, Payload};
+use std::error;
 
-pub struct Welcome;
+use crate::comm::{errors, identity::PlayerId, Message, MessageId, MessageRaw, Payload};
+
+/// Returned on failure.
+/// Carries stable error code and its human readable description.
+pub struct Error {
+    pub code: u32,
+    pub msg: String,
+}
+
+impl Error {
+    /// Creates Error response describing the given error.
+    /// Causes of the server's failures are only logged,
+    /// the client is sent the fixed description of the code.
+    pub fn new(err: &(dyn error::Error + 'static)) -> Error {
+        let code = errors::error_code(err);
+        if code < 500 {
+            return Error {
+                code,
+                msg: err.to_string(),
+            };
+        }
+        eprintln!("[{:^15}]: Server error {}: {}", "ErrorResponse", code, err);
+        let msg = match code {
+            errors::code::SERVER_FULL => "Server is full, try again later",
+            _ => "Internal server error",
+        };
+        Error {
+            code,
+            msg: String::from(msg),
+        }
+    }
+}
+
+impl Message for Error {
+    fn id(&self) -> MessageId {
+        0
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(4 + self.msg.len());
+        payload.extend(&self.code.to_le_bytes());
+        payload.extend_from_slice(self.msg.as_bytes());
+        payload
+    }
+}
+
+/// Closes the handshake.
+/// Carries the server's newest protocol version, the version
+/// agreed on and ids of all of the requests the server handles.
+pub struct Welcome {
+    pub server_version: u32,
+    pub negotiated_version: u32,
+    pub supported: Vec<MessageId>,
+}
 
 impl Message for Welcome {
     fn id(&self) -> MessageId {
         1
     }
 
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(12 + 4 * self.supported.len());
+        payload.extend(&self.server_version.to_le_bytes());
+        payload.extend(&self.negotiated_version.to_le_bytes());
+        payload.extend(&(self.supported.len() as u32).to_le_bytes());
+        for id in self.supported.iter() {
+            payload.extend(&id.to_le_bytes());
+        }
+        payload
+    }
+}
+
+/// Sent before the server closes the connection
+/// because it is shutting down.
+pub struct Goodbye;
+
+impl Message for Goodbye {
+    fn id(&self) -> MessageId {
+        2
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+/// Answer to Ping.
+pub struct Pong;
+
+impl Message for Pong {
+    fn id(&self) -> MessageId {
+        3
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+/// Answer to Auth. Carries the player's id and its token,
+/// which has to prefix every game affecting request.
+pub struct Authenticated {
+    pub player_id: PlayerId,
+    pub token: String,
+}
+
+impl Message for Authenticated {
+    fn id(&self) -> MessageId {
+        4
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(4 + self.token.len());
+        payload.extend(&self.player_id.to_le_bytes());
+        payload.extend_from_slice(self.token.as_bytes());
+        payload
+    }
+}
+
+/// Answer to Reconnect. Carries the player's id and
+/// the state updates missed while offline, oldest first.
+pub struct Resumed {
+    pub player_id: PlayerId,
+    /// Complete messages, header included.
+    pub missed: Vec<MessageRaw>,
+}
+
+impl Message for Resumed {
+    fn id(&self) -> MessageId {
+        5
+    }
+
+    fn payload(&self) -> Payload {
+        let missed_len: usize = self.missed.iter().map(Vec::len).sum();
+        let mut payload = Vec::with_capacity(8 + missed_len);
+        payload.extend(&self.player_id.to_le_bytes());
+        payload.extend(&(self.missed.len() as u32).to_le_bytes());
+        for update in self.missed.iter() {
+            payload.extend_from_slice(update);
+        }
+        payload
+    }
+}
+
+/// Lobby that can be joined.
+pub struct LobbyInfo {
+    pub lobby_id: u32,
+    pub num_of_players: u8,
+    pub taken_seats: u8,
+    pub board_size: (u32, u32),
+    /// Victory condition and the number of turns it depends on.
+    pub victory: (u8, u32),
+}
+
+/// Answer to ListLobbies.
+pub struct LobbyList {
+    pub lobbies: Vec<LobbyInfo>,
+}
+
+impl Message for LobbyList {
+    fn id(&self) -> MessageId {
+        6
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(4 + 19 * self.lobbies.len());
+        payload.extend(&(self.lobbies.len() as u32).to_le_bytes());
+        for lobby in self.lobbies.iter() {
+            payload.extend(&lobby.lobby_id.to_le_bytes());
+            payload.push(lobby.num_of_players);
+            payload.push(lobby.taken_seats);
+            payload.extend(&lobby.board_size.0.to_le_bytes());
+            payload.extend(&lobby.board_size.1.to_le_bytes());
+            payload.push(lobby.victory.0);
+            payload.extend(&lobby.victory.1.to_le_bytes());
+        }
+        payload
+    }
+}
+
+/// Answer to CreateLobby and JoinLobby.
+/// Seat is the player's id in the game.
+pub struct LobbyJoined {
+    pub lobby_id: u32,
+    pub seat: u8,
+}
+
+impl Message for LobbyJoined {
+    fn id(&self) -> MessageId {
+        7
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = self.lobby_id.to_le_bytes().to_vec();
+        payload.push(self.seat);
+        payload
+    }
+}
+
+/// Answer to LeaveLobby.
+pub struct LobbyLeft;
+
+impl Message for LobbyLeft {
+    fn id(&self) -> MessageId {
+        8
+    }
+
     fn payload(&self) -> Payload {
         Vec::new()
     }
 }
+
+/// Answer to Ready.
+pub struct ReadySet {
+    /// True if the sender was the last one to get ready.
+    pub game_started: bool,
+}
+
+impl Message for ReadySet {
+    fn id(&self) -> MessageId {
+        9
+    }
+
+    fn payload(&self) -> Payload {
+        vec![self.game_started as u8]
+    }
+}
+
+/// Answer to the unit orders.
+/// Order is carried out once the turn is resolved.
+pub struct OrderAccepted;
+
+impl Message for OrderAccepted {
+    fn id(&self) -> MessageId {
+        10
+    }
+
+    fn payload(&self) -> Payload {
+        Vec::new()
+    }
+}
+
+/// Answer to EndTurn.
+pub struct TurnEnded {
+    pub turn: u32,
+}
+
+impl Message for TurnEnded {
+    fn id(&self) -> MessageId {
+        11
+    }
+
+    fn payload(&self) -> Payload {
+        self.turn.to_le_bytes().to_vec()
+    }
+}
+
+/// Unit's place on the board.
+pub struct UnitPosition {
+    pub unit_id: u32,
+    pub owner_id: u8,
+    pub position: (u32, u32),
+}
+
+/// Sent to all of the game's players once the turn is resolved.
+pub struct UnitsMoved {
+    pub turn: u32,
+    pub units: Vec<UnitPosition>,
+}
+
+impl Message for UnitsMoved {
+    fn id(&self) -> MessageId {
+        12
+    }
+
+    fn payload(&self) -> Payload {
+        units_payload(self.turn, &self.units)
+    }
+}
+
+/// Sent to every player of the game once it starts, before TurnStarted,
+/// and to the player back in the game after reconnecting.
+/// Lists the units the player sees, so it can give orders in the turn.
+pub struct UnitsSeen {
+    pub turn: u32,
+    pub units: Vec<UnitPosition>,
+}
+
+impl Message for UnitsSeen {
+    fn id(&self) -> MessageId {
+        20
+    }
+
+    fn payload(&self) -> Payload {
+        units_payload(self.turn, &self.units)
+    }
+}
+
+fn units_payload(turn: u32, units: &[UnitPosition]) -> Payload {
+    let mut payload = Vec::with_capacity(8 + 13 * units.len());
+    payload.extend(&turn.to_le_bytes());
+    payload.extend(&(units.len() as u32).to_le_bytes());
+    for unit in units.iter() {
+        payload.extend(&unit.unit_id.to_le_bytes());
+        payload.push(unit.owner_id);
+        payload.extend(&unit.position.0.to_le_bytes());
+        payload.extend(&unit.position.1.to_le_bytes());
+    }
+    payload
+}
+
+/// Seat reported in GameOver if nobody won.
+pub const NO_SEAT: u8 = u8::MAX;
+
+/// Sent to all of the game's players once the game is over.
+/// Drawn game has no winner, its seat is NO_SEAT and player is 0.
+pub struct GameOver {
+    pub winner_seat: u8,
+    pub winner: PlayerId,
+}
+
+impl Message for GameOver {
+    fn id(&self) -> MessageId {
+        13
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = vec![self.winner_seat];
+        payload.extend(&self.winner.to_le_bytes());
+        payload
+    }
+}
+
+/// Sent to all of the game's players once they can give orders.
+pub struct TurnStarted {
+    pub turn: u32,
+    /// Time left for giving orders in milliseconds.
+    pub duration_ms: u32,
+}
+
+impl Message for TurnStarted {
+    fn id(&self) -> MessageId {
+        14
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = self.turn.to_le_bytes().to_vec();
+        payload.extend(&self.duration_ms.to_le_bytes());
+        payload
+    }
+}
+
+/// Sent to the lobby's players once one of them leaves it.
+pub struct PlayerLeft {
+    pub player: PlayerId,
+}
+
+impl Message for PlayerLeft {
+    fn id(&self) -> MessageId {
+        15
+    }
+
+    fn payload(&self) -> Payload {
+        self.player.to_le_bytes().to_vec()
+    }
+}
+
+/// Sent to all of the game's players for every battle
+/// fought in the resolved turn, before UnitsMoved.
+pub struct BattleResult {
+    pub attacker_id: u32,
+    pub defender_id: u32,
+    pub position: (u32, u32),
+    /// 0 if the attacker won, 1 if the defender won, 2 for a draw.
+    pub outcome: u8,
+}
+
+impl Message for BattleResult {
+    fn id(&self) -> MessageId {
+        16
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(17);
+        payload.extend(&self.attacker_id.to_le_bytes());
+        payload.extend(&self.defender_id.to_le_bytes());
+        payload.extend(&self.position.0.to_le_bytes());
+        payload.extend(&self.position.1.to_le_bytes());
+        payload.push(self.outcome);
+        payload
+    }
+}
+
+/// Sent to all of the game's players once one of them
+/// loses the last unit, before GameOver.
+pub struct PlayerEliminated {
+    pub seat: u8,
+    pub player: PlayerId,
+}
+
+impl Message for PlayerEliminated {
+    fn id(&self) -> MessageId {
+        17
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = vec![self.seat];
+        payload.extend(&self.player.to_le_bytes());
+        payload
+    }
+}
+
+/// Scenario lobbies can be created from.
+pub struct ScenarioInfo {
+    pub name: String,
+    pub num_of_players: u8,
+    pub board_size: (u32, u32),
+    /// Victory condition and the number of turns it depends on.
+    pub victory: (u8, u32),
+}
+
+/// Answer to ListScenarios.
+pub struct ScenarioList {
+    pub scenarios: Vec<ScenarioInfo>,
+}
+
+impl Message for ScenarioList {
+    fn id(&self) -> MessageId {
+        18
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::new();
+        payload.extend(&(self.scenarios.len() as u32).to_le_bytes());
+        for scenario in self.scenarios.iter() {
+            payload.extend(&(scenario.name.len() as u32).to_le_bytes());
+            payload.extend_from_slice(scenario.name.as_bytes());
+            payload.push(scenario.num_of_players);
+            payload.extend(&scenario.board_size.0.to_le_bytes());
+            payload.extend(&scenario.board_size.1.to_le_bytes());
+            payload.push(scenario.victory.0);
+            payload.extend(&scenario.victory.1.to_le_bytes());
+        }
+        payload
+    }
+}
+
+/// Sent to all of the game's players once the game starts,
+/// before TurnStarted.
+pub struct BoardSetup {
+    pub board_size: (u32, u32),
+    /// Terrain of the fields row by row, see the protocol description.
+    pub terrain: Vec<u8>,
+    /// Fields the players have to hold to win, if there are any.
+    pub objectives: Vec<(u32, u32)>,
+}
+
+impl Message for BoardSetup {
+    fn id(&self) -> MessageId {
+        19
+    }
+
+    fn payload(&self) -> Payload {
+        let mut payload = Vec::with_capacity(12 + self.terrain.len() + 8 * self.objectives.len());
+        payload.extend(&self.board_size.0.to_le_bytes());
+        payload.extend(&self.board_size.1.to_le_bytes());
+        payload.extend_from_slice(&self.terrain);
+        payload.extend(&(self.objectives.len() as u32).to_le_bytes());
+        for field in self.objectives.iter() {
+            payload.extend(&field.0.to_le_bytes());
+            payload.extend(&field.1.to_le_bytes());
+        }
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::comm::errors::{code, BadRequestError, InternalServerError, UnknownMessageId};
+
+    #[test]
+    fn error_payload_starts_with_code() {
+        let resp = Error::new(&BadRequestError::from(UnknownMessageId { id: 7 }));
+        let payload = resp.payload();
+        assert_eq!(&payload[..4], &code::UNKNOWN_MESSAGE.to_le_bytes());
+        assert_eq!(&payload[4..], resp.msg.as_bytes());
+    }
+
+    #[test]
+    fn internal_error_cause_is_not_sent() {
+        let resp = Error::new(&InternalServerError(Box::from("poisoned lock at 0x1234")));
+        assert_eq!(resp.code, code::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp.msg, "Internal server error");
+    }
+
+    #[test]
+    fn welcome_payload_lists_supported_messages() {
+        let resp = Welcome {
+            server_version: 2,
+            negotiated_version: 1,
+            supported: vec![0, 1],
+        };
+        let mut expected = Vec::new();
+        for val in &[2u32, 1, 2, 0, 1] {
+            expected.extend(&val.to_le_bytes());
+        }
+        assert_eq!(resp.payload(), expected);
+    }
+
+    #[test]
+    fn units_moved_payload_lists_units() {
+        let resp = UnitsMoved {
+            turn: 3,
+            units: vec![UnitPosition {
+                unit_id: 4,
+                owner_id: 1,
+                position: (5, 6),
+            }],
+        };
+        let mut expected = Vec::new();
+        for val in &[3u32, 1, 4] {
+            expected.extend(&val.to_le_bytes());
+        }
+        expected.push(1);
+        for val in &[5u32, 6] {
+            expected.extend(&val.to_le_bytes());
+        }
+        assert_eq!(resp.payload(), expected);
+    }
+
+    #[test]
+    fn scenario_list_payload_prefixes_names_with_length() {
+        let resp = ScenarioList {
+            scenarios: vec![ScenarioInfo {
+                name: String::from("duel"),
+                num_of_players: 2,
+                board_size: (3, 4),
+                victory: (2, 5),
+            }],
+        };
+        let mut expected = Vec::new();
+        for val in &[1u32, 4] {
+            expected.extend(&val.to_le_bytes());
+        }
+        expected.extend_from_slice(b"duel");
+        expected.push(2);
+        for val in &[3u32, 4] {
+            expected.extend(&val.to_le_bytes());
+        }
+        expected.push(2);
+        expected.extend(&5u32.to_le_bytes());
+        assert_eq!(resp.payload(), expected);
+    }
+}
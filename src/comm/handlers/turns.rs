@@ -0,0 +1,136 @@
+use std::time::Instant;
+
+use crate::comm::{
+    errors::HandlerError,
+    handlers::{lock_error, responses, Shared},
+    identity::PlayerId,
+    lobby::Lobby,
+    turn::TurnReport,
+};
+use crate::game::battle::Outcome;
+
+/// Resolves turns of all of the games whose planning time ran out
+/// and sends their outcome to the players.
+pub fn resolve_due(shared: &Shared, now: Instant) -> Result<(), HandlerError> {
+    let reports = shared
+        .lobbies
+        .write()
+        .map_err(lock_error)?
+        .resolve_due_turns(now);
+    for report in reports.iter() {
+        announce(shared, report)?;
+    }
+    Ok(())
+}
+
+/// Tells the game's players they can give orders in the turn.
+pub fn start(shared: &Shared, players: &[PlayerId], turn: u32) -> Result<(), HandlerError> {
+    let duration = shared.lobbies.read().map_err(lock_error)?.turn_duration();
+    shared.push_to(
+        players,
+        Box::new(responses::TurnStarted {
+            turn,
+            duration_ms: duration.as_millis() as u32,
+        }),
+    )
+}
+
+/// Lists the units the player sees in the lobby's running game.
+/// None if the game hasn't started or the player doesn't sit in the lobby.
+pub fn units_seen(lobby: &Lobby, player: PlayerId) -> Option<responses::UnitsSeen> {
+    let game = lobby.game.as_ref()?;
+    let seat = lobby.seat_of(player)?;
+    Some(responses::UnitsSeen {
+        turn: lobby.turn.as_ref().map_or(0, |turn| turn.number),
+        units: game
+            .visible_units(seat)
+            .iter()
+            .map(|unit| responses::UnitPosition {
+                unit_id: unit.id as u32,
+                owner_id: unit.owner_id,
+                position: (unit.position.0 as u32, unit.position.1 as u32),
+            })
+            .collect(),
+    })
+}
+
+/// Sends the battles and the units every player of the game can see,
+/// followed by the eliminated players and the result if the game is over
+/// or the start of the next turn otherwise.
+pub fn announce(shared: &Shared, report: &TurnReport) -> Result<(), HandlerError> {
+    eprintln!(
+        "[{:^15}]: Turn {} of lobby {} resolved.",
+        "TurnScheduler", report.turn, report.lobby
+    );
+    for (player, (battles, units)) in report
+        .players
+        .iter()
+        .zip(report.battles.iter().zip(report.units.iter()))
+    {
+        for battle in battles.iter() {
+            shared.push_to(
+                &[*player],
+                Box::new(responses::BattleResult {
+                    attacker_id: battle.attacker_id as u32,
+                    defender_id: battle.defender_id as u32,
+                    position: (battle.position.0 as u32, battle.position.1 as u32),
+                    outcome: match battle.outcome {
+                        Outcome::AttackerWon => 0,
+                        Outcome::DefenderWon => 1,
+                        Outcome::Draw => 2,
+                    },
+                }),
+            )?;
+        }
+        shared.push_to(
+            &[*player],
+            Box::new(responses::UnitsMoved {
+                turn: report.turn,
+                units: units
+                    .iter()
+                    .map(|unit| responses::UnitPosition {
+                        unit_id: unit.id as u32,
+                        owner_id: unit.owner_id,
+                        position: (unit.position.0 as u32, unit.position.1 as u32),
+                    })
+                    .collect(),
+            }),
+        )?;
+    }
+    for &(seat, player) in report.eliminated.iter() {
+        eprintln!(
+            "[{:^15}]: Player {} eliminated in lobby {}.",
+            "TurnScheduler", player, report.lobby
+        );
+        shared.push_to(
+            &report.players,
+            Box::new(responses::PlayerEliminated { seat, player }),
+        )?;
+    }
+    if !report.game_over {
+        return start(shared, &report.players, report.turn + 1);
+    }
+    let (winner_seat, winner) = match report.winner {
+        Some((seat, winner)) => {
+            eprintln!(
+                "[{:^15}]: Game in lobby {} won by player {}.",
+                "TurnScheduler", report.lobby, winner
+            );
+            (seat, winner)
+        }
+        None => {
+            eprintln!(
+                "[{:^15}]: Game in lobby {} ended in a draw.",
+                "TurnScheduler", report.lobby
+            );
+            (responses::NO_SEAT, 0)
+        }
+    };
+    shared.push_to(
+        &report.players,
+        Box::new(responses::GameOver {
+            winner_seat,
+            winner,
+        }),
+    )
+}
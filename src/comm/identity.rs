@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::comm::errors;
+
+/// Server wide player identifier.
+/// Stays the same for the whole lifetime of the server.
+pub type PlayerId = u32;
+
+const TOKEN_SEPARATOR: char = ':';
+
+/// Player known to the server.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub player_id: PlayerId,
+    pub name: String,
+    /// Random secret only the player knows.
+    identifier: String,
+}
+
+impl Identity {
+    /// Returns token the player proves its identity with.
+    /// It is `name:identifier` base64 encoded.
+    pub fn token(&self) -> String {
+        base64::encode(format!("{}{}{}", self.name, TOKEN_SEPARATOR, self.identifier))
+    }
+}
+
+/// Maps random player identifiers to player names.
+/// Player knows only its own identifier, so it can't
+/// send requests in the name of the others.
+#[derive(Default)]
+pub struct Registry {
+    players: HashMap<String, Identity>,
+    next_id: PlayerId,
+}
+
+impl Registry {
+    /// Creates identity for the new player.
+    /// Names have to be unique and can't contain the token separator.
+    pub fn register(&mut self, name: String) -> Result<Identity, errors::BadRequestError> {
+        if name.is_empty() || name.contains(TOKEN_SEPARATOR) {
+            return Err(errors::ReadError::from(format!(
+                "Player name can't be empty nor contain '{}'",
+                TOKEN_SEPARATOR
+            ))
+            .into());
+        }
+        if self.players.contains_key(&name) {
+            return Err(errors::NameTaken { name }.into());
+        }
+        let identity = Identity {
+            player_id: self.next_id,
+            name: name.clone(),
+            identifier: format!("{:016x}", rand::random::<u64>()),
+        };
+        self.next_id += 1;
+        self.players.insert(name, identity.clone());
+        Ok(identity)
+    }
+
+    /// Returns identity the token belongs to.
+    pub fn verify(&self, token: &[u8]) -> Result<&Identity, errors::InvalidToken> {
+        let decoded = base64::decode(token).map_err(|_| errors::InvalidToken)?;
+        let decoded = String::from_utf8(decoded).map_err(|_| errors::InvalidToken)?;
+        let sep = decoded.rfind(TOKEN_SEPARATOR).ok_or(errors::InvalidToken)?;
+        let (name, identifier) = (&decoded[..sep], &decoded[sep + 1..]);
+        match self.players.get(name) {
+            Some(identity) if identity.identifier == identifier => Ok(identity),
+            _ => Err(errors::InvalidToken),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn registered_player_token_is_verified() {
+        let mut registry = Registry::default();
+        let identity = registry.register(String::from("alice")).unwrap();
+        let verified = registry.verify(identity.token().as_bytes()).unwrap();
+        assert_eq!(verified.player_id, identity.player_id);
+        assert_eq!(verified.name, "alice");
+    }
+
+    #[test]
+    fn token_is_name_and_identifier_base64_encoded() {
+        let mut registry = Registry::default();
+        let identity = registry.register(String::from("alice")).unwrap();
+        let decoded = String::from_utf8(base64::decode(identity.token()).unwrap()).unwrap();
+        assert_eq!(decoded, format!("alice:{}", identity.identifier));
+    }
+
+    #[test]
+    fn players_get_distinct_ids() {
+        let mut registry = Registry::default();
+        let alice = registry.register(String::from("alice")).unwrap();
+        let bob = registry.register(String::from("bob")).unwrap();
+        assert_ne!(alice.player_id, bob.player_id);
+    }
+
+    #[test]
+    fn taken_name_is_rejected() {
+        let mut registry = Registry::default();
+        registry.register(String::from("alice")).unwrap();
+        let err = registry.register(String::from("alice")).err().unwrap();
+        assert_eq!(errors::error_code(&err), errors::code::NAME_TAKEN);
+    }
+
+    #[test]
+    fn name_with_separator_is_rejected() {
+        let mut registry = Registry::default();
+        assert!(registry.register(String::from("al:ice")).is_err());
+        assert!(registry.register(String::new()).is_err());
+    }
+
+    #[test]
+    fn forged_token_is_rejected() {
+        let mut registry = Registry::default();
+        registry.register(String::from("alice")).unwrap();
+        let forged = base64::encode("alice:0000000000000000");
+        assert!(registry.verify(forged.as_bytes()).is_err());
+        assert!(registry.verify(b"not base64!").is_err());
+        assert!(registry.verify(base64::encode("bob:1").as_bytes()).is_err());
+    }
+}
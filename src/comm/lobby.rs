@@ -0,0 +1,689 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use fast_from_derive::BadRequest;
+
+use crate::comm::{
+    errors::BadRequestError,
+    identity::PlayerId,
+    turn::{Turn, TurnReport},
+};
+use crate::game::{
+    mapgen, scenario::Scenario, terrain::Terrain, unit::Category, victory::Victory, Game,
+};
+
+pub type LobbyId = u32;
+
+/// Maximum number of players in the single game.
+pub const MAX_PLAYERS: u8 = 8;
+/// Maximum length of the board's side.
+pub const MAX_BOARD_SIDE: usize = 1024;
+/// Units every player starts with, placed in a row.
+const STARTING_ARMY: [Category; 3] = [Category::Cavalry, Category::Knight, Category::Pickerman];
+
+/// Returned on lobby requests that cannot be fulfilled.
+#[derive(Debug, BadRequest)]
+pub enum LobbyError {
+    /// Requested player count or board size is not allowed.
+    InvalidSettings(String),
+    /// There is no lobby with the given id.
+    NoSuchLobby(LobbyId),
+    /// All of the lobby's seats are taken.
+    LobbyFull(LobbyId),
+    /// Player already sits in the lobby with the given id.
+    AlreadyInLobby(LobbyId),
+    /// Player doesn't sit in any lobby.
+    NotInLobby,
+    /// Game in the lobby is already running.
+    GameStarted(LobbyId),
+    /// Game in the lobby is not running yet.
+    GameNotStarted(LobbyId),
+    /// Player already ended the turn with the given number.
+    TurnEnded(u32),
+    /// There is no scenario with the given name.
+    NoSuchScenario(String),
+}
+
+impl Error for LobbyError {}
+
+impl fmt::Display for LobbyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LobbyError::InvalidSettings(reason) => write!(f, "Invalid game settings: {}", reason),
+            LobbyError::NoSuchLobby(id) => write!(f, "Lobby {} doesn't exist", id),
+            LobbyError::LobbyFull(id) => write!(f, "Lobby {} is full", id),
+            LobbyError::AlreadyInLobby(id) => write!(f, "Already sitting in lobby {}", id),
+            LobbyError::NotInLobby => write!(f, "Not sitting in any lobby"),
+            LobbyError::GameStarted(id) => write!(f, "Game in lobby {} already started", id),
+            LobbyError::GameNotStarted(id) => write!(f, "Game in lobby {} didn't start yet", id),
+            LobbyError::TurnEnded(turn) => write!(f, "Turn {} already ended", turn),
+            LobbyError::NoSuchScenario(name) => write!(f, "Scenario {} doesn't exist", name),
+        }
+    }
+}
+
+/// Player's place in the lobby.
+/// Index of the seat is the player's id in the game.
+#[derive(Clone, Debug)]
+pub struct Seat {
+    pub player: PlayerId,
+    pub ready: bool,
+}
+
+/// Game waiting for the players or already running.
+pub struct Lobby {
+    pub id: LobbyId,
+    pub num_of_players: u8,
+    pub board_size: (usize, usize),
+    /// Condition the game is won by.
+    pub victory: Victory,
+    /// Setup the game is started from.
+    /// Without it the board is all plains and every player
+    /// gets the starting army.
+    pub scenario: Option<Scenario>,
+    pub seats: Vec<Seat>,
+    /// Set once all of the seats are taken and ready.
+    pub game: Option<Game>,
+    /// Turn being planned in the running game.
+    pub turn: Option<Turn>,
+}
+
+impl Lobby {
+    /// Checks if players can still join.
+    pub fn is_open(&self) -> bool {
+        self.game.is_none() && self.seats.len() < self.num_of_players as usize
+    }
+
+    /// Returns seat index of the player, which is its id in the game.
+    pub fn seat_of(&self, player: PlayerId) -> Option<u8> {
+        self.seats
+            .iter()
+            .position(|seat| seat.player == player)
+            .map(|seat| seat as u8)
+    }
+
+    /// Returns players of the lobby ordered by their seats.
+    pub fn players(&self) -> Vec<PlayerId> {
+        self.seats.iter().map(|seat| seat.player).collect()
+    }
+
+    fn all_ready(&self) -> bool {
+        self.seats.len() == self.num_of_players as usize && self.seats.iter().all(|s| s.ready)
+    }
+
+    /// Creates the game and deploys starting army of every player
+    /// or the units of the scenario.
+    fn start(&mut self, now: Instant) {
+        if let Some(scenario) = self.scenario.as_ref() {
+            self.game = Some(scenario.build());
+            self.turn = Some(Turn::new(0, now));
+            return;
+        }
+        let mut game = Game::with_victory(self.num_of_players, self.board_size, self.victory.clone());
+        let (_, height) = self.board_size;
+        let last = (self.num_of_players - 1) as usize;
+        for owner in 0..self.num_of_players {
+            let y = owner as usize * (height - 1) / last;
+            for (x, &category) in STARTING_ARMY.iter().enumerate() {
+                game.add_unit(owner, (x, y), category)
+                    .expect("lobby settings guarantee army fits the board");
+            }
+        }
+        self.game = Some(game);
+        self.turn = Some(Turn::new(0, now));
+    }
+
+    /// Resolves the turn and starts the next one
+    /// unless the game is over.
+    fn resolve_turn(&mut self, now: Instant) -> Option<TurnReport> {
+        let players = self.players();
+        let game = self.game.as_mut()?;
+        let number = self.turn.as_ref()?.number;
+        let report = TurnReport::resolve(self.id, number, players, game);
+        self.turn = Some(Turn::new(number + 1, now));
+        Some(report)
+    }
+}
+
+/// All of the games hosted by the server.
+/// Player can sit in at most one lobby at a time.
+pub struct LobbyManager {
+    lobbies: BTreeMap<LobbyId, Lobby>,
+    players: HashMap<PlayerId, LobbyId>,
+    next_id: LobbyId,
+    /// Time players have to plan their orders.
+    turn_duration: Duration,
+}
+
+impl LobbyManager {
+    pub fn new(turn_duration: Duration) -> LobbyManager {
+        LobbyManager {
+            lobbies: BTreeMap::new(),
+            players: HashMap::new(),
+            next_id: 0,
+            turn_duration,
+        }
+    }
+
+    /// Creates lobby for the game with the given settings.
+    /// Board has to fit starting army of every player
+    /// and objectives of the game, if there are any.
+    pub fn create(
+        &mut self,
+        num_of_players: u8,
+        board_size: (usize, usize),
+        victory: Victory,
+    ) -> Result<LobbyId, LobbyError> {
+        check_settings(num_of_players, board_size, &victory)?;
+        Ok(self.open(num_of_players, board_size, victory, None))
+    }
+
+    /// Creates lobby for the game played on the map generated from the seed,
+    /// see mapgen::generate. Settings are checked the same way create does.
+    /// Objectives are cleared of the terrain units can't enter.
+    pub fn create_generated(
+        &mut self,
+        num_of_players: u8,
+        board_size: (usize, usize),
+        victory: Victory,
+        seed: u64,
+    ) -> Result<LobbyId, LobbyError> {
+        check_settings(num_of_players, board_size, &victory)?;
+        let mut scenario = mapgen::generate(seed, board_size, num_of_players);
+        if let Victory::Objectives { fields, .. } = &victory {
+            for &field in fields.iter() {
+                if scenario.map.get(field).movement_cost().is_none() {
+                    scenario.map.set(field, Terrain::Plains);
+                }
+            }
+        }
+        scenario.victory = victory.clone();
+        Ok(self.open(num_of_players, board_size, victory, Some(scenario)))
+    }
+
+    /// Creates lobby for the game started from the scenario.
+    pub fn create_from(&mut self, scenario: Scenario) -> Result<LobbyId, LobbyError> {
+        let (width, height) = scenario.board_size();
+        if scenario.num_of_players > MAX_PLAYERS {
+            return Err(LobbyError::InvalidSettings(format!(
+                "number of players has to be between 2 and {}",
+                MAX_PLAYERS
+            )));
+        }
+        if width > MAX_BOARD_SIDE || height > MAX_BOARD_SIDE {
+            return Err(LobbyError::InvalidSettings(format!(
+                "board side can't be longer than {}",
+                MAX_BOARD_SIDE
+            )));
+        }
+        Ok(self.open(
+            scenario.num_of_players,
+            (width, height),
+            scenario.victory.clone(),
+            Some(scenario),
+        ))
+    }
+
+    fn open(
+        &mut self,
+        num_of_players: u8,
+        board_size: (usize, usize),
+        victory: Victory,
+        scenario: Option<Scenario>,
+    ) -> LobbyId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.lobbies.insert(
+            id,
+            Lobby {
+                id,
+                num_of_players,
+                board_size,
+                victory,
+                scenario,
+                seats: Vec::new(),
+                game: None,
+                turn: None,
+            },
+        );
+        id
+    }
+
+    /// Returns time players have to plan their orders.
+    pub fn turn_duration(&self) -> Duration {
+        self.turn_duration
+    }
+
+    /// Returns lobbies players can still join, ordered by id.
+    pub fn list_open(&self) -> Vec<&Lobby> {
+        self.lobbies.values().filter(|l| l.is_open()).collect()
+    }
+
+    /// Returns lobby with the given id.
+    pub fn get(&self, id: LobbyId) -> Option<&Lobby> {
+        self.lobbies.get(&id)
+    }
+
+    /// Returns lobby the player sits in.
+    pub fn lobby_of(&self, player: PlayerId) -> Option<&Lobby> {
+        self.players.get(&player).and_then(|id| self.lobbies.get(id))
+    }
+
+    /// Returns running game the player takes part in
+    /// along with the player's id in it.
+    /// Players who ended the turn can't give orders until the next one.
+    pub fn game_of_mut(&mut self, player: PlayerId) -> Result<(&mut Game, u8), LobbyError> {
+        let (lobby, seat) = self.running_lobby_of(player)?;
+        match (lobby.game.as_mut(), lobby.turn.as_ref()) {
+            (_, Some(turn)) if turn.has_ended(seat) => Err(LobbyError::TurnEnded(turn.number)),
+            (Some(game), _) => Ok((game, seat)),
+            (None, _) => Err(LobbyError::GameNotStarted(lobby.id)),
+        }
+    }
+
+    /// Marks the player as done with giving orders in the current turn.
+    /// Returns number of the ended turn and its outcome
+    /// if the player was the last one to end it.
+    pub fn end_turn(
+        &mut self,
+        player: PlayerId,
+        now: Instant,
+    ) -> Result<(u32, Option<TurnReport>), LobbyError> {
+        let turn_duration = self.turn_duration;
+        let (lobby, seat) = self.running_lobby_of(player)?;
+        let turn = match lobby.turn.as_mut() {
+            Some(val) => val,
+            None => return Err(LobbyError::GameNotStarted(lobby.id)),
+        };
+        if turn.has_ended(seat) {
+            return Err(LobbyError::TurnEnded(turn.number));
+        }
+        turn.end(seat);
+        let number = turn.number;
+        if !turn.is_over(lobby.num_of_players, turn_duration, now) {
+            return Ok((number, None));
+        }
+        let report = lobby.resolve_turn(now);
+        if let Some(report) = report.as_ref() {
+            self.close_finished(report);
+        }
+        Ok((number, report))
+    }
+
+    /// Resolves turns of all of the games whose planning time ran out.
+    pub fn resolve_due_turns(&mut self, now: Instant) -> Vec<TurnReport> {
+        let turn_duration = self.turn_duration;
+        let reports: Vec<TurnReport> = self
+            .lobbies
+            .values_mut()
+            .filter(|lobby| match lobby.turn.as_ref() {
+                Some(turn) => turn.is_over(lobby.num_of_players, turn_duration, now),
+                None => false,
+            })
+            .filter_map(|lobby| lobby.resolve_turn(now))
+            .collect();
+        for report in reports.iter() {
+            self.close_finished(report);
+        }
+        reports
+    }
+
+    /// Closes lobby of the finished game, so its players can play again.
+    fn close_finished(&mut self, report: &TurnReport) {
+        if !report.game_over {
+            return;
+        }
+        self.lobbies.remove(&report.lobby);
+        for player in report.players.iter() {
+            self.players.remove(player);
+        }
+    }
+
+    /// Returns lobby with the running game the player takes part in
+    /// along with the player's seat.
+    fn running_lobby_of(&mut self, player: PlayerId) -> Result<(&mut Lobby, u8), LobbyError> {
+        let id = *self.players.get(&player).ok_or(LobbyError::NotInLobby)?;
+        let lobby = self.lobbies.get_mut(&id).ok_or(LobbyError::NoSuchLobby(id))?;
+        let seat = lobby.seat_of(player).ok_or(LobbyError::NotInLobby)?;
+        if lobby.game.is_none() {
+            return Err(LobbyError::GameNotStarted(id));
+        }
+        Ok((lobby, seat))
+    }
+
+    /// Seats the creator in the lobby it has just created.
+    /// Lobby is closed if the creator can't take the seat,
+    /// so no lobby is left without players.
+    pub fn host(&mut self, id: LobbyId, player: PlayerId) -> Result<u8, LobbyError> {
+        let seat = self.join(id, player);
+        if seat.is_err() && self.get(id).is_some_and(|lobby| lobby.seats.is_empty()) {
+            self.lobbies.remove(&id);
+        }
+        seat
+    }
+
+    /// Seats player in the lobby. Returns the seat index.
+    pub fn join(&mut self, id: LobbyId, player: PlayerId) -> Result<u8, LobbyError> {
+        if let Some(&current) = self.players.get(&player) {
+            return Err(LobbyError::AlreadyInLobby(current));
+        }
+        let lobby = self.lobbies.get_mut(&id).ok_or(LobbyError::NoSuchLobby(id))?;
+        if lobby.game.is_some() {
+            return Err(LobbyError::GameStarted(id));
+        }
+        if !lobby.is_open() {
+            return Err(LobbyError::LobbyFull(id));
+        }
+        lobby.seats.push(Seat {
+            player,
+            ready: false,
+        });
+        self.players.insert(player, id);
+        Ok((lobby.seats.len() - 1) as u8)
+    }
+
+    /// Frees the player's seat. Empty lobbies are closed.
+    /// Players can't leave the running game.
+    pub fn leave(&mut self, player: PlayerId) -> Result<LobbyId, LobbyError> {
+        let id = *self.players.get(&player).ok_or(LobbyError::NotInLobby)?;
+        let lobby = self.lobbies.get_mut(&id).ok_or(LobbyError::NoSuchLobby(id))?;
+        if lobby.game.is_some() {
+            return Err(LobbyError::GameStarted(id));
+        }
+        lobby.seats.retain(|seat| seat.player != player);
+        if lobby.seats.is_empty() {
+            self.lobbies.remove(&id);
+        }
+        self.players.remove(&player);
+        Ok(id)
+    }
+
+    /// Marks player as ready or not.
+    /// Starts the game once all of the seats are taken and ready.
+    /// Returns true if the game started.
+    pub fn set_ready(
+        &mut self,
+        player: PlayerId,
+        ready: bool,
+        now: Instant,
+    ) -> Result<bool, LobbyError> {
+        let id = *self.players.get(&player).ok_or(LobbyError::NotInLobby)?;
+        let lobby = self.lobbies.get_mut(&id).ok_or(LobbyError::NoSuchLobby(id))?;
+        if lobby.game.is_some() {
+            return Err(LobbyError::GameStarted(id));
+        }
+        for seat in lobby.seats.iter_mut().filter(|s| s.player == player) {
+            seat.ready = ready;
+        }
+        if lobby.all_ready() {
+            lobby.start(now);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+/// Checks settings of the lobby created by the players.
+fn check_settings(
+    num_of_players: u8,
+    board_size: (usize, usize),
+    victory: &Victory,
+) -> Result<(), LobbyError> {
+    let (width, height) = board_size;
+    if !(2..=MAX_PLAYERS).contains(&num_of_players) {
+        return Err(LobbyError::InvalidSettings(format!(
+            "number of players has to be between 2 and {}",
+            MAX_PLAYERS
+        )));
+    }
+    if width > MAX_BOARD_SIDE || height > MAX_BOARD_SIDE {
+        return Err(LobbyError::InvalidSettings(format!(
+            "board side can't be longer than {}",
+            MAX_BOARD_SIDE
+        )));
+    }
+    if width < STARTING_ARMY.len() || height < num_of_players as usize {
+        return Err(LobbyError::InvalidSettings(format!(
+            "board has to be at least {}x{}",
+            STARTING_ARMY.len(),
+            num_of_players
+        )));
+    }
+    match victory {
+        Victory::Objectives { fields, .. } if fields.is_empty() => {
+            return Err(LobbyError::InvalidSettings(String::from(
+                "there has to be at least one objective",
+            )));
+        }
+        Victory::Objectives { fields, .. } if fields.iter().any(|&(x, y)| x >= width || y >= height) => {
+            return Err(LobbyError::InvalidSettings(String::from(
+                "objectives have to lie on the board",
+            )));
+        }
+        Victory::Objectives { turns: 0, .. } | Victory::TurnLimit(0) => {
+            return Err(LobbyError::InvalidSettings(String::from(
+                "number of turns has to be positive",
+            )));
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const TURN: Duration = Duration::from_secs(60);
+
+    /// Returns manager with the game of players 1 and 2 started at the given time.
+    fn running_game(now: Instant) -> (LobbyManager, LobbyId) {
+        let mut lobbies = LobbyManager::new(TURN);
+        let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        for player in 1..3 {
+            lobbies.join(id, player).unwrap();
+            lobbies.set_ready(player, true, now).unwrap();
+        }
+        (lobbies, id)
+    }
+
+    #[test]
+    fn created_lobby_is_listed_until_full() {
+        let mut lobbies = LobbyManager::new(TURN);
+        let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        assert_eq!(lobbies.list_open().len(), 1);
+        assert_eq!(lobbies.join(id, 1).unwrap(), 0);
+        assert_eq!(lobbies.join(id, 2).unwrap(), 1);
+        assert!(lobbies.list_open().is_empty());
+        match lobbies.join(id, 3) {
+            Err(LobbyError::LobbyFull(_)) => (),
+            _ => panic!("full lobby should not be joined"),
+        }
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let mut lobbies = LobbyManager::new(TURN);
+        assert!(lobbies.create(1, (10, 10), Victory::LastStanding).is_err());
+        assert!(lobbies.create(MAX_PLAYERS + 1, (10, 10), Victory::LastStanding).is_err());
+        assert!(lobbies.create(2, (2, 10), Victory::LastStanding).is_err());
+        assert!(lobbies.create(4, (10, 3), Victory::LastStanding).is_err());
+        assert!(lobbies.create(2, (MAX_BOARD_SIDE + 1, 10), Victory::LastStanding).is_err());
+        assert!(lobbies.create(2, (10, 10), Victory::TurnLimit(0)).is_err());
+        let outside = Victory::Objectives {
+            fields: vec![(10, 0)],
+            turns: 1,
+        };
+        assert!(lobbies.create(2, (10, 10), outside).is_err());
+    }
+
+    #[test]
+    fn player_sits_in_one_lobby_at_a_time() {
+        let mut lobbies = LobbyManager::new(TURN);
+        let first = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        let second = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        lobbies.join(first, 1).unwrap();
+        match lobbies.join(second, 1) {
+            Err(LobbyError::AlreadyInLobby(id)) => assert_eq!(id, first),
+            _ => panic!("player should not sit in two lobbies"),
+        }
+    }
+
+    #[test]
+    fn lobby_is_closed_if_its_creator_cannot_take_the_seat() {
+        let mut lobbies = LobbyManager::new(TURN);
+        let first = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        assert_eq!(lobbies.host(first, 1).unwrap(), 0);
+        let second = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        assert!(matches!(
+            lobbies.host(second, 1),
+            Err(LobbyError::AlreadyInLobby(_))
+        ));
+        assert!(lobbies.get(second).is_none());
+        assert!(lobbies.get(first).is_some());
+    }
+
+    #[test]
+    fn empty_lobby_is_closed_after_leave() {
+        let mut lobbies = LobbyManager::new(TURN);
+        let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        lobbies.join(id, 1).unwrap();
+        assert_eq!(lobbies.leave(1).unwrap(), id);
+        assert!(lobbies.get(id).is_none());
+        assert!(lobbies.lobby_of(1).is_none());
+        match lobbies.leave(1) {
+            Err(LobbyError::NotInLobby) => (),
+            _ => panic!("player not in lobby should not leave"),
+        }
+    }
+
+    #[test]
+    fn game_starts_when_all_seats_are_ready() {
+        let mut lobbies = LobbyManager::new(TURN);
+        let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        lobbies.join(id, 1).unwrap();
+        assert!(!lobbies.set_ready(1, true, Instant::now()).unwrap());
+        lobbies.join(id, 2).unwrap();
+        assert!(lobbies.set_ready(2, true, Instant::now()).unwrap());
+        let game = lobbies.lobbies.get(&id).unwrap().game.as_ref().unwrap();
+        assert_eq!(game.get_unit(0).unwrap().position, (0, 0));
+        assert_eq!(game.get_unit(3).unwrap().position, (0, 9));
+        assert_eq!(game.get_unit(3).unwrap().owner_id, 1);
+        assert_eq!(lobbies.game_of_mut(2).unwrap().1, 1);
+        match lobbies.leave(1) {
+            Err(LobbyError::GameStarted(_)) => (),
+            _ => panic!("player should not leave running game"),
+        }
+    }
+
+    #[test]
+    fn game_is_started_from_the_scenario() {
+        let scenario = Scenario::parse(
+            r#"
+players = 2
+board = { width = 3, height = 2, terrain = ".~.\n...\n" }
+victory = { kind = "turn_limit", turns = 5 }
+units = [
+    { owner = 0, category = "knight", position = [0, 0] },
+    { owner = 1, category = "cavalry", position = [2, 0] },
+]
+"#,
+        )
+        .unwrap();
+        let mut lobbies = LobbyManager::new(TURN);
+        let id = lobbies.create_from(scenario).unwrap();
+        let lobby = lobbies.get(id).unwrap();
+        assert_eq!(lobby.board_size, (3, 2));
+        assert_eq!(lobby.victory, Victory::TurnLimit(5));
+        for player in 1..3 {
+            lobbies.join(id, player).unwrap();
+            lobbies.set_ready(player, true, Instant::now()).unwrap();
+        }
+        let game = lobbies.lobbies.get(&id).unwrap().game.as_ref().unwrap();
+        assert!(game.get_unit(2).is_err());
+        assert_eq!(game.get_unit(1).unwrap().position, (2, 0));
+        assert_eq!(game.map().get((1, 0)), Terrain::Water);
+    }
+
+    #[test]
+    fn game_is_played_on_the_generated_map() {
+        let victory = Victory::Objectives {
+            fields: vec![(10, 7)],
+            turns: 3,
+        };
+        let mut lobbies = LobbyManager::new(TURN);
+        assert!(lobbies.create_generated(2, (2, 10), Victory::LastStanding, 1).is_err());
+        for seed in 0..10 {
+            let id = lobbies.create_generated(3, (20, 15), victory.clone(), seed).unwrap();
+            let scenario = lobbies.get(id).unwrap().scenario.as_ref().unwrap();
+            let generated = mapgen::generate(seed, (20, 15), 3);
+            assert_eq!(scenario.units, generated.units);
+            assert_eq!(scenario.victory, victory);
+            assert!(scenario.map.get((10, 7)).movement_cost().is_some());
+        }
+    }
+
+    #[test]
+    fn game_does_not_start_until_everyone_is_ready() {
+        let mut lobbies = LobbyManager::new(TURN);
+        let id = lobbies.create(2, (10, 10), Victory::LastStanding).unwrap();
+        lobbies.join(id, 1).unwrap();
+        lobbies.join(id, 2).unwrap();
+        assert!(!lobbies.set_ready(1, true, Instant::now()).unwrap());
+        assert!(!lobbies.set_ready(1, false, Instant::now()).unwrap());
+        assert!(!lobbies.set_ready(2, true, Instant::now()).unwrap());
+        assert!(lobbies.lobbies.get(&id).unwrap().game.is_none());
+        match lobbies.game_of_mut(1) {
+            Err(LobbyError::GameNotStarted(_)) => (),
+            _ => panic!("game should not be running"),
+        }
+    }
+
+    #[test]
+    fn turn_is_resolved_once_everyone_ended_it() {
+        let now = Instant::now();
+        let (mut lobbies, id) = running_game(now);
+        lobbies.game_of_mut(1).unwrap().0.move_unit(0, (0, 1)).unwrap();
+        assert_eq!(lobbies.end_turn(1, now).unwrap().0, 0);
+        let report = match lobbies.end_turn(2, now).unwrap() {
+            (0, Some(report)) => report,
+            _ => panic!("turn should be resolved"),
+        };
+        assert_eq!(report.lobby, id);
+        assert_eq!(report.players, vec![1, 2]);
+        assert_eq!(report.units[0][0].position, (0, 1));
+        assert!(report.winner.is_none());
+        assert_eq!(lobbies.end_turn(1, now).unwrap().0, 1);
+    }
+
+    #[test]
+    fn player_who_ended_turn_cannot_give_orders() {
+        let now = Instant::now();
+        let (mut lobbies, _) = running_game(now);
+        lobbies.end_turn(1, now).unwrap();
+        match lobbies.game_of_mut(1) {
+            Err(LobbyError::TurnEnded(0)) => (),
+            _ => panic!("orders should wait for the next turn"),
+        }
+        match lobbies.end_turn(1, now) {
+            Err(LobbyError::TurnEnded(0)) => (),
+            _ => panic!("turn should not be ended twice"),
+        }
+        assert!(lobbies.game_of_mut(2).is_ok());
+    }
+
+    #[test]
+    fn turn_is_resolved_when_time_runs_out() {
+        let now = Instant::now();
+        let (mut lobbies, id) = running_game(now);
+        assert!(lobbies.resolve_due_turns(now).is_empty());
+        let reports = lobbies.resolve_due_turns(now + TURN);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].lobby, id);
+        assert_eq!(reports[0].turn, 0);
+        assert!(lobbies.resolve_due_turns(now + TURN).is_empty());
+    }
+}
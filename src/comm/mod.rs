@@ -1,14 +1,38 @@
 use std::{
-    net::TcpListener,
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
     sync::{Arc, RwLock},
     thread,
+    time::{Duration, Instant},
 };
 
 use crate::config;
 
+#[cfg(feature = "async-server")]
+mod async_server;
 mod connection;
 mod errors;
+mod framing;
 mod handlers;
+mod identity;
+mod lobby;
+mod pool;
+mod push;
+mod scenarios;
+mod session;
+mod shutdown;
+mod turn;
+
+#[cfg(feature = "async-server")]
+pub use self::async_server::AsyncServer;
+pub use self::connection::MSG_HEADER_LEN;
+pub use self::shutdown::ShutdownHandle;
+
+/// Newest version of the protocol the server speaks.
+/// Sent in Welcome, so the clients can tell what to expect.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest version of the protocol the server still speaks.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
 
 /// Alias for vector of bytes.
 /// Used to stress that the vector should contain
@@ -41,11 +65,14 @@ pub trait Response: Message {}
 impl<T> Response for T where T: Message {}
 
 /// Handles incoming connections and dispatches them
-/// to Worker threads.
+/// to the pool of worker threads.
 pub struct Server {
     listener: TcpListener,
     req_dispatcher: Arc<RwLock<handlers::Dispatcher>>,
-    thread_handles: Vec<thread::JoinHandle<()>>,
+    pool: pool::WorkerPool,
+    settings: connection::Settings,
+    shutdown: ShutdownHandle,
+    grace_period: Duration,
 }
 
 impl Server {
@@ -58,51 +85,109 @@ impl Server {
             "Initialization", filename
         );
         let config = config::ServerConfig::from_file(filename.as_str()).unwrap();
+        Self::from_config(config)
+    }
+
+    /// Creates new server instance from the already read configuration.
+    pub fn from_config(config: config::ServerConfig) -> Server {
         let listener = TcpListener::bind(config.to_string()).unwrap();
+        listener.set_nonblocking(true).unwrap();
         eprintln!("[{:^15}]: Created.", "Initialization");
         Server {
             listener,
-            req_dispatcher: Arc::new(RwLock::new(handlers::init::new_dispatcher())),
-
-            thread_handles: Vec::new(),
+            req_dispatcher: Arc::new(RwLock::new(handlers::init::new_dispatcher(&config))),
+            pool: pool::WorkerPool::new(config.worker_threads, config.pending_connections),
+            settings: connection::Settings::from(&config),
+            shutdown: ShutdownHandle::new(),
+            grace_period: Duration::from_millis(config.shutdown_grace_ms),
         }
     }
 
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns handle which can be used to stop the server
+    /// from another thread.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
     /// Run waits for incoming connections.
-    /// If one appears handles it in new thread.
+    /// If one appears queues it to be handled by the worker pool.
+    /// Returns after the shutdown was triggered and
+    /// all of the connections were closed.
     pub fn run(&mut self) {
         eprintln!("[{:^15}]: Staring listening.", "Server");
         let mut conn_count: usize = 0;
-        for stream in self.listener.incoming() {
-            let stream = stream.unwrap();
-            eprintln!("[{:^15}]: New connection established.", "Server");
-            let conn_handler = connection::Handler::new(
-                connection::Context::new(conn_count),
-                self.req_dispatcher.clone(),
+        while !self.shutdown.is_triggered() {
+            self.pool.reap();
+            self.tick();
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    self.dispatch_connection(stream, conn_count);
+                    conn_count += 1;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(shutdown::POLL_INTERVAL);
+                }
+                Err(err) => eprintln!(
+                    "[{:^15}]: Error while accepting connection! {}",
+                    "Server", err
+                ),
+            }
+        }
+        eprintln!("[{:^15}]: Shutting down.", "Server");
+        self.pool.drain(self.grace_period);
+        eprintln!("[{:^15}]: Stopped.", "Server");
+    }
+
+    /// Lets the games whose planning time ran out resolve their turns.
+    fn tick(&self) {
+        match self.req_dispatcher.read() {
+            Ok(dispatcher) => dispatcher.tick(Instant::now()),
+            Err(err) => eprintln!("[{:^15}]: Could not resolve turns! {}", "Server", err),
+        }
+    }
+
+    fn dispatch_connection(&mut self, stream: TcpStream, conn_id: usize) {
+        eprintln!("[{:^15}]: New connection established.", "Server");
+        if let Err(err) = Self::prepare_stream(&stream, &self.settings) {
+            eprintln!(
+                "[{:^15}]: Could not set up the connection! {}",
+                "Server", err
             );
-            self.thread_handles.push(thread::spawn(move || {
-                eprintln!("[{:^15}]: New thread handling connection!", "HandlerThread");
-                conn_handler.handle_connection(stream);
-                eprintln!("[{:^15}]: Connection handled!", "HandlerThread");
-            }));
-            conn_count += 1;
+            return;
+        }
+        let handler = connection::Handler::new(
+            connection::Context::new(conn_id),
+            self.settings,
+            self.req_dispatcher.clone(),
+            self.shutdown.clone(),
+        );
+        if let Err(job) = self.pool.execute(pool::Job { handler, stream }) {
+            eprintln!("[{:^15}]: No free workers. Refusing connection.", "Server");
+            job.handler.refuse(job.stream, &errors::ServerFull);
         }
     }
+
+    /// Switches stream to blocking mode with read timeout,
+    /// so the handler can periodically check for the shutdown
+    /// and idle clients. Writes to the client that doesn't
+    /// receive them time out as well.
+    fn prepare_stream(stream: &TcpStream, settings: &connection::Settings) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(shutdown::POLL_INTERVAL))?;
+        stream.set_write_timeout(Some(settings.write_timeout))
+    }
 }
 
-// todo: should actually stop these threads immediatly
-// not wait for them
 impl Drop for Server {
-    /// Joins on currently running connection
-    /// handling threads.
+    /// Stops currently running connection
+    /// handling threads and joins them.
     fn drop(&mut self) {
-        for handle in self.thread_handles.drain(..) {
-            if let Err(err) = handle.join() {
-                eprintln!(
-                    "[{:^15}]: Error while joining a thread! {:?}",
-                    "Server", err
-                );
-            }
-        }
+        self.shutdown.trigger();
+        self.pool.drain(self.grace_period);
     }
 }
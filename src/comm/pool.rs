@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    net::{self, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::comm::{connection, shutdown};
+
+/// Connection waiting for a free worker.
+pub struct Job {
+    pub handler: connection::Handler,
+    pub stream: TcpStream,
+}
+
+/// Streams of the connections currently handled by the workers
+/// mapped by the connection id.
+type ActiveConnections = Arc<Mutex<HashMap<usize, TcpStream>>>;
+
+/// Fixed size pool of threads handling connections.
+/// Accepted connections wait in the bounded queue
+/// until one of the workers is free.
+pub struct WorkerPool {
+    sender: Option<SyncSender<Job>>,
+    receiver: Arc<Mutex<Receiver<Job>>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    active: ActiveConnections,
+}
+
+impl WorkerPool {
+    /// Creates pool with size worker threads and the queue
+    /// able to hold queue_len pending connections.
+    ///
+    /// Panics if size is 0.
+    pub fn new(size: usize, queue_len: usize) -> WorkerPool {
+        assert!(size > 0);
+        let (sender, receiver) = mpsc::sync_channel(queue_len);
+        let mut pool = WorkerPool {
+            sender: Some(sender),
+            receiver: Arc::new(Mutex::new(receiver)),
+            workers: Vec::with_capacity(size),
+            active: Arc::new(Mutex::new(HashMap::new())),
+        };
+        for _ in 0..size {
+            let worker = pool.spawn_worker();
+            pool.workers.push(worker);
+        }
+        pool
+    }
+
+    /// Queues connection to be handled by the first free worker.
+    /// If the queue is full the job is given back.
+    pub fn execute(&self, job: Job) -> Result<(), Box<Job>> {
+        let sender = match &self.sender {
+            Some(val) => val,
+            None => return Err(Box::new(job)),
+        };
+        match sender.try_send(job) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(job)) | Err(TrySendError::Disconnected(job)) => {
+                Err(Box::new(job))
+            }
+        }
+    }
+
+    /// Replaces workers whose threads have finished, for example
+    /// because of a panic, with the new ones.
+    pub fn reap(&mut self) {
+        for i in 0..self.workers.len() {
+            if !self.workers[i].is_finished() || self.sender.is_none() {
+                continue;
+            }
+            let worker = self.spawn_worker();
+            let finished = std::mem::replace(&mut self.workers[i], worker);
+            if let Err(err) = finished.join() {
+                eprintln!(
+                    "[{:^15}]: Reaped worker that panicked! {:?}",
+                    "WorkerPool", err
+                );
+            }
+        }
+    }
+
+    /// Stops accepting new jobs and waits the grace period
+    /// for the workers to finish. Then severs connections
+    /// that are still being handled and joins all of the workers.
+    pub fn drain(&mut self, grace_period: Duration) {
+        drop(self.sender.take());
+        let deadline = Instant::now() + grace_period;
+        while Instant::now() < deadline && self.workers.iter().any(|w| !w.is_finished()) {
+            thread::sleep(shutdown::POLL_INTERVAL);
+        }
+        if let Ok(active) = self.active.lock() {
+            for stream in active.values() {
+                eprintln!(
+                    "[{:^15}]: Grace period exceeded. Severing connection.",
+                    "WorkerPool"
+                );
+                let _ = stream.shutdown(net::Shutdown::Both);
+            }
+        }
+        for worker in self.workers.drain(..) {
+            if let Err(err) = worker.join() {
+                eprintln!(
+                    "[{:^15}]: Error while joining a thread! {:?}",
+                    "WorkerPool", err
+                );
+            }
+        }
+    }
+
+    fn spawn_worker(&self) -> thread::JoinHandle<()> {
+        let receiver = self.receiver.clone();
+        let active = self.active.clone();
+        thread::spawn(move || loop {
+            let job = match receiver.lock() {
+                Ok(guard) => guard.recv(),
+                Err(_) => return,
+            };
+            match job {
+                Ok(job) => Self::run_job(job, &active),
+                Err(_) => return,
+            }
+        })
+    }
+
+    fn run_job(job: Job, active: &ActiveConnections) {
+        let id = job.handler.id();
+        if let (Ok(stream), Ok(mut active)) = (job.stream.try_clone(), active.lock()) {
+            active.insert(id, stream);
+        }
+        let _guard = ActiveGuard { id, active };
+        eprintln!("[{:^15}]: Worker handling connection!", "WorkerPool");
+        job.handler.handle_connection(job.stream);
+        eprintln!("[{:^15}]: Connection handled!", "WorkerPool");
+    }
+}
+
+/// Removes connection from the active ones when dropped,
+/// even if its handler panicked.
+struct ActiveGuard<'a> {
+    id: usize,
+    active: &'a ActiveConnections,
+}
+
+impl<'a> Drop for ActiveGuard<'a> {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.active.lock() {
+            active.remove(&self.id);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::comm::MessageRaw;
+
+/// Serialized messages the server sends to the connection
+/// on its own, without any request.
+/// Connection writes them in between its responses.
+#[derive(Clone, Default)]
+pub struct PushQueue {
+    frames: Arc<Mutex<VecDeque<MessageRaw>>>,
+}
+
+impl PushQueue {
+    /// Queues complete message, header included.
+    pub fn push(&self, frame: MessageRaw) {
+        match self.frames.lock() {
+            Ok(mut frames) => frames.push_back(frame),
+            Err(err) => eprintln!("[{:^15}]: Could not queue the message! {}", "PushQueue", err),
+        }
+    }
+
+    /// Returns all of the queued messages, oldest first.
+    pub fn take(&self) -> Vec<MessageRaw> {
+        match self.frames.lock() {
+            Ok(mut frames) => frames.drain(..).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Push queues of all of the open connections.
+#[derive(Default)]
+pub struct PushQueues {
+    queues: HashMap<usize, PushQueue>,
+}
+
+impl PushQueues {
+    /// Creates queue of the new connection.
+    pub fn open(&mut self, conn_id: usize) -> PushQueue {
+        let queue = PushQueue::default();
+        self.queues.insert(conn_id, queue.clone());
+        queue
+    }
+
+    /// Drops queue of the closed connection.
+    /// Returns messages the connection didn't get to send, oldest first.
+    pub fn close(&mut self, conn_id: usize) -> Vec<MessageRaw> {
+        match self.queues.remove(&conn_id) {
+            Some(queue) => queue.take(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Queues message for the connection.
+    /// Returns false if the connection is already closed.
+    pub fn push(&self, conn_id: usize, frame: MessageRaw) -> bool {
+        match self.queues.get(&conn_id) {
+            Some(queue) => {
+                queue.push(frame);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn pushed_messages_are_taken_in_order() {
+        let mut queues = PushQueues::default();
+        let queue = queues.open(1);
+        assert!(queues.push(1, vec![1]));
+        assert!(queues.push(1, vec![2]));
+        assert_eq!(queue.take(), vec![vec![1], vec![2]]);
+        assert!(queue.take().is_empty());
+    }
+
+    #[test]
+    fn closed_connection_gets_no_messages() {
+        let mut queues = PushQueues::default();
+        let queue = queues.open(1);
+        queues.close(1);
+        assert!(!queues.push(1, vec![1]));
+        assert!(queue.take().is_empty());
+    }
+
+    #[test]
+    fn unsent_messages_are_returned_on_close() {
+        let mut queues = PushQueues::default();
+        queues.open(1);
+        queues.push(1, vec![1]);
+        queues.push(1, vec![2]);
+        assert_eq!(queues.close(1), vec![vec![1], vec![2]]);
+        assert!(queues.close(1).is_empty());
+    }
+}
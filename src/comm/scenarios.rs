@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::comm::lobby::LobbyError;
+use crate::game::scenario::Scenario;
+
+/// Extension of the scenario files.
+const EXTENSION: &str = "toml";
+
+/// Scenario files players can start games from.
+/// Scenario is named after its file, without the extension.
+pub struct Catalog {
+    dir: PathBuf,
+}
+
+impl Catalog {
+    pub fn new(dir: &str) -> Catalog {
+        Catalog {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Returns valid scenarios of the directory ordered by name.
+    /// Files that could not be read are skipped.
+    pub fn list(&self) -> Vec<(String, Scenario)> {
+        let mut scenarios: Vec<(String, Scenario)> = self
+            .names()
+            .into_iter()
+            .filter_map(|name| match self.read(&name) {
+                Ok(scenario) => Some((name, scenario)),
+                Err(err) => {
+                    eprintln!("[{:^15}]: Skipping scenario {}! {}", "Scenarios", name, err);
+                    None
+                }
+            })
+            .collect();
+        scenarios.sort_by(|a, b| a.0.cmp(&b.0));
+        scenarios
+    }
+
+    /// Reads scenario with the given name.
+    /// Only the scenarios from the directory can be read.
+    pub fn load(&self, name: &str) -> Result<Scenario, LobbyError> {
+        if !self.names().iter().any(|listed| listed == name) {
+            return Err(LobbyError::NoSuchScenario(String::from(name)));
+        }
+        self.read(name)
+            .map_err(|err| LobbyError::InvalidSettings(format!("scenario {}: {}", name, err)))
+    }
+
+    fn read(&self, name: &str) -> Result<Scenario, Box<dyn Error>> {
+        let path = self.dir.join(name).with_extension(EXTENSION);
+        Scenario::from_file(&path.to_string_lossy())
+    }
+
+    /// Returns names of the scenario files in the directory.
+    fn names(&self) -> Vec<String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(val) => val,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SCENARIO: &str = r#"
+players = 2
+board = { width = 3, height = 2 }
+units = [
+    { owner = 0, category = "cavalry", position = [0, 0] },
+    { owner = 1, category = "knight", position = [2, 1] },
+]
+"#;
+
+    /// Returns directory with the given files.
+    fn dir_with(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files.iter() {
+            fs::write(dir.path().join(name), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn valid_scenarios_are_listed_by_name() {
+        let dir = dir_with(&[
+            ("duel.toml", SCENARIO),
+            ("broken.toml", "players = 1"),
+            ("notes.txt", SCENARIO),
+            ("arena.toml", SCENARIO),
+        ]);
+        let catalog = Catalog::new(dir.path().to_str().unwrap());
+        let names: Vec<String> = catalog.list().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["arena", "duel"]);
+    }
+
+    #[test]
+    fn missing_directory_has_no_scenarios() {
+        assert!(Catalog::new("no/such/scenarios").list().is_empty());
+    }
+
+    #[test]
+    fn only_listed_scenarios_are_loaded() {
+        let dir = dir_with(&[("duel.toml", SCENARIO), ("broken.toml", "players = 1")]);
+        let catalog = Catalog::new(dir.path().to_str().unwrap());
+        assert_eq!(catalog.load("duel").unwrap().num_of_players, 2);
+        assert!(matches!(
+            catalog.load("broken"),
+            Err(LobbyError::InvalidSettings(_))
+        ));
+        assert!(matches!(
+            catalog.load("../duel"),
+            Err(LobbyError::NoSuchScenario(_))
+        ));
+    }
+
+    #[test]
+    fn shipped_scenarios_are_valid() {
+        let catalog = Catalog::new("scenarios");
+        for name in catalog.names() {
+            assert!(catalog.load(&name).is_ok(), "scenario {} is invalid", name);
+        }
+        assert!(!catalog.list().is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::comm::{connection, errors, identity::PlayerId, MessageRaw};
+
+/// Player's seat on the server.
+/// Outlives the connection, so the player can pick up
+/// where it left off after reconnecting.
+struct Session {
+    /// Connection currently acting for the player.
+    connection: Option<usize>,
+    /// State of the connection when it was detached.
+    state: connection::State,
+    /// Serialized state updates missed while offline, oldest first.
+    backlog: VecDeque<MessageRaw>,
+}
+
+/// Sessions of all of the authenticated players.
+pub struct Sessions {
+    sessions: HashMap<PlayerId, Session>,
+    backlog_len: usize,
+}
+
+impl Sessions {
+    /// Creates registry keeping at most backlog_len
+    /// missed updates for every disconnected player.
+    pub fn new(backlog_len: usize) -> Sessions {
+        Sessions {
+            sessions: HashMap::new(),
+            backlog_len,
+        }
+    }
+
+    /// Creates session of the newly authenticated player
+    /// and attaches the connection to it.
+    pub fn open(&mut self, player: PlayerId, ctx: &connection::Context) {
+        self.sessions.insert(
+            player,
+            Session {
+                connection: Some(ctx.id),
+                state: ctx.state,
+                backlog: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Checks if the connection is the one acting for the player.
+    /// Connection stops acting for the player
+    /// once other one resumes its session.
+    pub fn is_attached(&self, player: PlayerId, conn_id: usize) -> bool {
+        self.sessions
+            .get(&player)
+            .is_some_and(|s| s.connection == Some(conn_id))
+    }
+
+    /// Marks player of the closed connection as offline.
+    /// Does nothing if the session was already taken over by other connection.
+    pub fn detach(&mut self, ctx: &connection::Context) {
+        let player = match ctx.player {
+            Some(val) => val,
+            None => return,
+        };
+        if let Some(session) = self.sessions.get_mut(&player) {
+            if session.connection == Some(ctx.id) {
+                session.connection = None;
+                session.state = ctx.state;
+            }
+        }
+    }
+
+    /// Attaches the connection to the player's session.
+    /// Takes over the session even if the old connection
+    /// is still open, as it is most likely already dead.
+    /// Returns state the connection should continue in
+    /// and the updates missed in the meantime.
+    pub fn resume(
+        &mut self,
+        player: PlayerId,
+        conn_id: usize,
+    ) -> Result<(connection::State, Vec<MessageRaw>), errors::InvalidToken> {
+        let session = self
+            .sessions
+            .get_mut(&player)
+            .ok_or(errors::InvalidToken)?;
+        session.connection = Some(conn_id);
+        Ok((session.state, session.backlog.drain(..).collect()))
+    }
+
+    /// Keeps state update for the offline player.
+    /// Drops the oldest one if the backlog is full.
+    /// Returns connection of the online player
+    /// the update has to be pushed to instead.
+    pub fn post(&mut self, player: PlayerId, update: MessageRaw) -> Option<usize> {
+        let session = self.sessions.get_mut(&player)?;
+        if session.connection.is_some() {
+            return session.connection;
+        }
+        Self::keep(session, self.backlog_len, update);
+        None
+    }
+
+    /// Keeps state update the player's connection couldn't take
+    /// for the player to get once it resumes the session.
+    pub fn keep_missed(&mut self, player: PlayerId, update: MessageRaw) {
+        if let Some(session) = self.sessions.get_mut(&player) {
+            Self::keep(session, self.backlog_len, update);
+        }
+    }
+
+    fn keep(session: &mut Session, backlog_len: usize, update: MessageRaw) {
+        if backlog_len == 0 {
+            return;
+        }
+        if session.backlog.len() == backlog_len {
+            session.backlog.pop_front();
+        }
+        session.backlog.push_back(update);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn context(id: usize, player: PlayerId, state: connection::State) -> connection::Context {
+        let mut ctx = connection::Context::new(id);
+        ctx.player = Some(player);
+        ctx.state = state;
+        ctx
+    }
+
+    #[test]
+    fn resumed_session_restores_state() {
+        let mut sessions = Sessions::new(4);
+        let ctx = context(1, 7, connection::State::Authenticated);
+        sessions.open(7, &ctx);
+        sessions.detach(&context(1, 7, connection::State::InLobby));
+        let (state, missed) = sessions.resume(7, 2).unwrap();
+        assert_eq!(state, connection::State::InLobby);
+        assert!(missed.is_empty());
+        assert!(sessions.is_attached(7, 2));
+        assert!(!sessions.is_attached(7, 1));
+    }
+
+    #[test]
+    fn updates_are_delivered_directly_to_online_player() {
+        let mut sessions = Sessions::new(4);
+        sessions.open(7, &context(1, 7, connection::State::Authenticated));
+        assert_eq!(sessions.post(7, vec![1]), Some(1));
+    }
+
+    #[test]
+    fn missed_updates_are_replayed_in_order() {
+        let mut sessions = Sessions::new(4);
+        let ctx = context(1, 7, connection::State::Authenticated);
+        sessions.open(7, &ctx);
+        sessions.detach(&ctx);
+        assert_eq!(sessions.post(7, vec![1]), None);
+        assert_eq!(sessions.post(7, vec![2]), None);
+        let (_, missed) = sessions.resume(7, 2).unwrap();
+        assert_eq!(missed, vec![vec![1], vec![2]]);
+        let (_, missed) = sessions.resume(7, 3).unwrap();
+        assert!(missed.is_empty());
+    }
+
+    #[test]
+    fn backlog_keeps_only_the_newest_updates() {
+        let mut sessions = Sessions::new(2);
+        let ctx = context(1, 7, connection::State::Authenticated);
+        sessions.open(7, &ctx);
+        sessions.detach(&ctx);
+        for update in 0..5 {
+            sessions.post(7, vec![update]);
+        }
+        let (_, missed) = sessions.resume(7, 2).unwrap();
+        assert_eq!(missed, vec![vec![3], vec![4]]);
+    }
+
+    #[test]
+    fn closing_taken_over_connection_keeps_session_attached() {
+        let mut sessions = Sessions::new(4);
+        let old = context(1, 7, connection::State::InGame);
+        sessions.open(7, &old);
+        sessions.resume(7, 2).unwrap();
+        sessions.detach(&old);
+        assert!(sessions.is_attached(7, 2));
+    }
+
+    #[test]
+    fn unknown_player_cannot_resume() {
+        let mut sessions = Sessions::new(4);
+        assert!(sessions.resume(7, 1).is_err());
+    }
+}
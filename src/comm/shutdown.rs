@@ -0,0 +1,52 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// How often blocked server parts check
+/// whether the shutdown was triggered.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cloneable handle used to stop the server.
+/// Can be triggered from any thread, for example
+/// from the signal handler.
+#[derive(Clone, Default)]
+pub struct ShutdownHandle {
+    triggered: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    pub fn new() -> ShutdownHandle {
+        ShutdownHandle {
+            triggered: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Requests the server and all of its connections to stop.
+    pub fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks if the shutdown was requested.
+    pub fn is_triggered(&self) -> bool {
+        self.triggered.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn trigger_is_visible_in_clones() {
+        let handle = ShutdownHandle::new();
+        let clone = handle.clone();
+        assert!(!clone.is_triggered());
+        handle.trigger();
+        assert!(clone.is_triggered());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::comm::{identity::PlayerId, lobby::LobbyId};
+use crate::game::{battle::Battle, victory::GameResult, Game};
+
+/// Planning phase of the single turn.
+/// Players give orders until all of them end the turn
+/// or the time runs out.
+pub struct Turn {
+    pub number: u32,
+    started: Instant,
+    /// Seats of the players done with giving orders.
+    ended: HashSet<u8>,
+}
+
+impl Turn {
+    pub fn new(number: u32, now: Instant) -> Turn {
+        Turn {
+            number,
+            started: now,
+            ended: HashSet::new(),
+        }
+    }
+
+    /// Marks the player as done with giving orders.
+    pub fn end(&mut self, seat: u8) {
+        self.ended.insert(seat);
+    }
+
+    /// Checks if the player already ended the turn.
+    pub fn has_ended(&self, seat: u8) -> bool {
+        self.ended.contains(&seat)
+    }
+
+    /// Checks if the turn should be resolved.
+    pub fn is_over(&self, num_of_players: u8, duration: Duration, now: Instant) -> bool {
+        self.ended.len() == num_of_players as usize
+            || now.saturating_duration_since(self.started) >= duration
+    }
+}
+
+/// Unit as seen after the turn was resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnitReport {
+    pub id: usize,
+    pub owner_id: u8,
+    pub position: (usize, usize),
+}
+
+/// Outcome of the resolved turn sent to all of the game's players.
+#[derive(Debug)]
+pub struct TurnReport {
+    pub lobby: LobbyId,
+    pub turn: u32,
+    /// Players of the game ordered by their seats.
+    pub players: Vec<PlayerId>,
+    /// Units each of the players can see, indexed by seat.
+    pub units: Vec<Vec<UnitReport>>,
+    /// Battles fought in the turn each of the players witnessed,
+    /// in order and indexed by seat.
+    pub battles: Vec<Vec<Battle>>,
+    /// Seats and ids of the players who lost their last unit in the turn.
+    pub eliminated: Vec<(u8, PlayerId)>,
+    /// Set once the game is over, either won or drawn.
+    pub game_over: bool,
+    /// Seat and id of the player who won, if the game is over.
+    pub winner: Option<(u8, PlayerId)>,
+}
+
+impl TurnReport {
+    /// Carries out the orders given in the turn and reports the outcome.
+    /// Players learn only about the units they see once the turn is resolved.
+    /// Battle is witnessed by the owners of the units fighting it
+    /// and by the players who see its field.
+    pub fn resolve(lobby: LobbyId, turn: u32, players: Vec<PlayerId>, game: &mut Game) -> TurnReport {
+        let owners: HashMap<usize, u8> = game
+            .units()
+            .iter()
+            .map(|unit| (unit.id, unit.owner_id))
+            .collect();
+        let fought = game.resolve_moves();
+        let seats = 0..players.len() as u8;
+        let units = seats
+            .clone()
+            .map(|seat| {
+                game.visible_units(seat)
+                    .into_iter()
+                    .map(|unit| UnitReport {
+                        id: unit.id,
+                        owner_id: unit.owner_id,
+                        position: unit.position,
+                    })
+                    .collect()
+            })
+            .collect();
+        let battles = seats
+            .map(|seat| {
+                fought
+                    .iter()
+                    .filter(|battle| {
+                        owners.get(&battle.attacker_id) == Some(&seat)
+                            || owners.get(&battle.defender_id) == Some(&seat)
+                            || game.is_visible(seat, battle.position)
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let verdict = game.game_over();
+        let eliminated = verdict
+            .eliminated
+            .iter()
+            .map(|&seat| (seat, players[seat as usize]))
+            .collect();
+        let winner = match verdict.result {
+            Some(GameResult::Won(seat)) => Some((seat, players[seat as usize])),
+            _ => None,
+        };
+        TurnReport {
+            lobby,
+            turn,
+            players,
+            units,
+            battles,
+            eliminated,
+            game_over: verdict.result.is_some(),
+            winner,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::unit::Category;
+
+    #[test]
+    fn turn_is_over_once_everyone_ended_it() {
+        let now = Instant::now();
+        let mut turn = Turn::new(0, now);
+        turn.end(0);
+        assert!(turn.has_ended(0));
+        assert!(!turn.is_over(2, Duration::from_secs(60), now));
+        turn.end(1);
+        assert!(turn.is_over(2, Duration::from_secs(60), now));
+    }
+
+    #[test]
+    fn turn_is_over_once_time_runs_out() {
+        let now = Instant::now();
+        let turn = Turn::new(0, now);
+        let duration = Duration::from_secs(60);
+        assert!(!turn.is_over(2, duration, now + Duration::from_secs(59)));
+        assert!(turn.is_over(2, duration, now + duration));
+    }
+
+    #[test]
+    fn players_are_reported_only_units_they_see() {
+        let mut game = Game::new(2, (30, 30));
+        game.add_unit(0, (0, 0), Category::Knight).unwrap();
+        game.add_unit(1, (20, 20), Category::Knight).unwrap();
+        game.add_unit(1, (2, 2), Category::Knight).unwrap();
+        let report = TurnReport::resolve(0, 0, vec![7, 8], &mut game);
+        let ids: Vec<Vec<usize>> = report
+            .units
+            .iter()
+            .map(|units| units.iter().map(|unit| unit.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![0, 2], vec![0, 1, 2]]);
+    }
+}
@@ -5,10 +5,11 @@ use std::fmt;
 
 use serde_derive::Deserialize;
 
+use super::comm::MSG_HEADER_LEN;
 use super::helpers::file;
 
 /// Type Alias making return types more compact
-pub type ConfigResult<T> = Result<T, Box<Error>>;
+pub type ConfigResult<T> = Result<T, Box<dyn Error>>;
 
 /// Game and server config taken from the config file.
 #[derive(Debug, Deserialize)]
@@ -24,13 +25,109 @@ pub struct ServerConfig {
     pub address: String,
     /// Number of the server port.
     pub port: u16,
+    /// Milliseconds given to in-flight requests to finish
+    /// after the shutdown was triggered.
+    #[serde(default = "default_shutdown_grace_ms")]
+    pub shutdown_grace_ms: u64,
+    /// Number of threads handling connections.
+    /// Each of them handles one connection at a time.
+    #[serde(default = "default_worker_threads")]
+    pub worker_threads: usize,
+    /// Number of accepted connections waiting for a free worker.
+    /// Connections over this limit are refused.
+    #[serde(default = "default_pending_connections")]
+    pub pending_connections: usize,
+    /// Maximum length of the single message in bytes, header included.
+    /// Longer messages are rejected before they are read.
+    #[serde(default = "default_max_message_len")]
+    pub max_message_len: usize,
+    /// Heartbeat window. Milliseconds the client can stay silent
+    /// before it is considered gone and disconnected.
+    #[serde(default = "default_idle_timeout_ms")]
+    pub idle_timeout_ms: u64,
+    /// Milliseconds the client has to send the rest
+    /// of the already started message.
+    #[serde(default = "default_read_timeout_ms")]
+    pub read_timeout_ms: u64,
+    /// Milliseconds the server waits for the client
+    /// to receive the response.
+    #[serde(default = "default_write_timeout_ms")]
+    pub write_timeout_ms: u64,
+    /// Number of state updates kept for the disconnected player
+    /// and replayed once it reconnects. Older ones are dropped.
+    #[serde(default = "default_session_backlog_len")]
+    pub session_backlog_len: usize,
+    /// Time players have to plan their orders in milliseconds.
+    /// Turn is resolved earlier once every player ends it.
+    #[serde(default = "default_turn_duration_ms")]
+    pub turn_duration_ms: u64,
+    /// Directory the scenario files players can start games from are read from.
+    /// Only files with the .toml extension are listed.
+    #[serde(default = "default_scenario_dir")]
+    pub scenario_dir: String,
 }
 
+fn default_shutdown_grace_ms() -> u64 {
+    5000
+}
+
+fn default_worker_threads() -> usize {
+    8
+}
+
+fn default_pending_connections() -> usize {
+    16
+}
+
+fn default_max_message_len() -> usize {
+    64 * 1024
+}
+
+fn default_idle_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_read_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_write_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_session_backlog_len() -> usize {
+    64
+}
+
+fn default_turn_duration_ms() -> u64 {
+    60000
+}
+
+fn default_scenario_dir() -> String {
+    String::from("scenarios")
+}
+
+/// Returned when the option is set to the value the server can't work with.
+#[derive(Debug)]
+pub struct InvalidOption {
+    pub option: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid {}: {}", self.option, self.reason)
+    }
+}
+
+impl Error for InvalidOption {}
+
 impl Config {
     /// Reads config from file. Returns Config on success, Err otherwise.
     pub fn from_file(file_name: &str) -> ConfigResult<Config> {
         let content = file::read(file_name)?;
         let config: Config = toml::from_str(content.as_str())?;
+        config.server.validate()?;
 
         Ok(config)
     }
@@ -38,8 +135,39 @@ impl Config {
 
 impl ServerConfig {
     /// Creates new ServerConfig.
+    /// Options other than address and port are set to their defaults.
     pub fn new(address: String, port: u16) -> ServerConfig {
-        ServerConfig { address, port }
+        ServerConfig {
+            address,
+            port,
+            shutdown_grace_ms: default_shutdown_grace_ms(),
+            worker_threads: default_worker_threads(),
+            pending_connections: default_pending_connections(),
+            max_message_len: default_max_message_len(),
+            idle_timeout_ms: default_idle_timeout_ms(),
+            read_timeout_ms: default_read_timeout_ms(),
+            write_timeout_ms: default_write_timeout_ms(),
+            session_backlog_len: default_session_backlog_len(),
+            turn_duration_ms: default_turn_duration_ms(),
+            scenario_dir: default_scenario_dir(),
+        }
+    }
+
+    /// Checks the options the server couldn't start or work with.
+    pub fn validate(&self) -> Result<(), InvalidOption> {
+        if self.worker_threads == 0 {
+            return Err(InvalidOption {
+                option: "worker_threads",
+                reason: String::from("at least one worker is needed"),
+            });
+        }
+        if self.max_message_len < MSG_HEADER_LEN {
+            return Err(InvalidOption {
+                option: "max_message_len",
+                reason: format!("message header alone takes {} bytes", MSG_HEADER_LEN),
+            });
+        }
+        Ok(())
     }
 
     /// Reads ServerConfig from the config file.
@@ -105,17 +233,77 @@ mod tests {
         let config = ServerConfig {
             address: String::from("10.1.1.1"),
             port: 1111,
+            shutdown_grace_ms: 100,
+            worker_threads: 2,
+            pending_connections: 4,
+            max_message_len: 1024,
+            idle_timeout_ms: 1000,
+            read_timeout_ms: 200,
+            write_timeout_ms: 300,
+            session_backlog_len: 8,
+            turn_duration_ms: 5000,
+            scenario_dir: String::from("maps"),
         };
         assert_eq!(config.address, "10.1.1.1");
         assert_eq!(config.port, 1111);
+        assert_eq!(config.shutdown_grace_ms, 100);
+        assert_eq!(config.worker_threads, 2);
+        assert_eq!(config.pending_connections, 4);
+        assert_eq!(config.max_message_len, 1024);
+        assert_eq!(config.idle_timeout_ms, 1000);
+        assert_eq!(config.read_timeout_ms, 200);
+        assert_eq!(config.write_timeout_ms, 300);
+        assert_eq!(config.session_backlog_len, 8);
+        assert_eq!(config.turn_duration_ms, 5000);
+        assert_eq!(config.scenario_dir, String::from("maps"));
+    }
+
+    #[test]
+    fn server_config_optional_fields_have_defaults() {
+        let file = file::create_temp_with_content(
+            r#"[server]
+        address = "10.0.0.1"
+        port = 6543"#,
+        )
+        .unwrap();
+        let config = ServerConfig::from_file(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.shutdown_grace_ms, default_shutdown_grace_ms());
+        assert_eq!(config.worker_threads, default_worker_threads());
+        assert_eq!(config.pending_connections, default_pending_connections());
+        assert_eq!(config.max_message_len, default_max_message_len());
+        assert_eq!(config.idle_timeout_ms, default_idle_timeout_ms());
+        assert_eq!(config.read_timeout_ms, default_read_timeout_ms());
+        assert_eq!(config.write_timeout_ms, default_write_timeout_ms());
+        assert_eq!(config.session_backlog_len, default_session_backlog_len());
+        assert_eq!(config.turn_duration_ms, default_turn_duration_ms());
+        assert_eq!(config.scenario_dir, default_scenario_dir());
+    }
+
+    #[test]
+    fn server_config_without_workers_is_rejected() {
+        let file = file::create_temp_with_content(
+            r#"[server]
+        address = "10.0.0.1"
+        port = 6543
+        worker_threads = 0"#,
+        )
+        .unwrap();
+        let err = ServerConfig::from_file(file.path().to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("worker_threads"));
+    }
+
+    #[test]
+    fn max_message_len_has_to_fit_the_header() {
+        let mut config = ServerConfig::new(String::from("127.0.0.1"), 1234);
+        config.max_message_len = MSG_HEADER_LEN - 1;
+        assert_eq!(config.validate().unwrap_err().option, "max_message_len");
+        config.max_message_len = MSG_HEADER_LEN;
+        assert!(config.validate().is_ok());
     }
 
     #[test]
     fn server_config_to_string_trait_is_implemented_right() {
-        let config = ServerConfig {
-            address: String::from("127.0.0.1"),
-            port: 1234,
-        };
+        let config = ServerConfig::new(String::from("127.0.0.1"), 1234);
         assert_eq!("127.0.0.1:1234", config.to_string());
     }
 }
@@ -0,0 +1,25 @@
+/// Defines battle outcomes reported after the turn.
+use super::helpers::Coords;
+
+/// How the battle ended.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// Defender was destroyed, attacker took its field.
+    AttackerWon,
+    /// Attacker was destroyed.
+    DefenderWon,
+    /// Units are of the same category. Attacker was repelled
+    /// and both of them stay in play.
+    Draw,
+}
+
+/// Battle fought while resolving the turn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Battle {
+    /// Unit that run into the enemy while in Attack state.
+    pub attacker_id: usize,
+    pub defender_id: usize,
+    /// Field occupied by the defender.
+    pub position: Coords,
+    pub outcome: Outcome,
+}
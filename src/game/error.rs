@@ -17,6 +17,11 @@ pub enum GameError {
     /// Requested move cannot be done due
     /// to units stats.
     MoveOutsideUnitsReach(usize, usize),
+    /// Unit with the given id is stuck in a blockade
+    /// and can't be given orders.
+    UnitBlocked(usize),
+    /// Units can't enter the field at the given coordinates.
+    ImpassableTerrain(usize, usize),
 }
 
 impl Error for GameError {}
@@ -27,3 +32,66 @@ impl fmt::Display for GameError {
         write!(f, "{:?}", &self)
     }
 }
+
+/// Returned if the map could not be read.
+#[derive(Debug)]
+pub enum MapError {
+    /// Map has no rows.
+    Empty,
+    /// Row with the given index is longer or shorter than the first one.
+    RaggedRow(usize),
+    /// Character at the given coordinates is not any of the terrains.
+    UnknownTile(char, usize, usize),
+}
+
+impl Error for MapError {}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "Map is empty"),
+            MapError::RaggedRow(y) => write!(f, "Row {} differs in length from the first one", y),
+            MapError::UnknownTile(tile, x, y) => {
+                write!(f, "Unknown tile '{}' at ({}, {})", tile, x, y)
+            }
+        }
+    }
+}
+
+/// Returned if the scenario could not be read.
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// Scenario is not valid TOML or some of its entries
+    /// are missing or of the wrong type.
+    Syntax(toml::de::Error),
+    /// Value of the entry under the given path is not allowed,
+    /// e.g. "units[2].position".
+    InvalidEntry { entry: String, reason: String },
+}
+
+impl ScenarioError {
+    /// Creates error pointing to the invalid entry.
+    pub fn invalid(entry: impl Into<String>, reason: impl Into<String>) -> ScenarioError {
+        ScenarioError::InvalidEntry {
+            entry: entry.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Error for ScenarioError {}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Syntax(err) => write!(f, "Malformed scenario: {}", err),
+            ScenarioError::InvalidEntry { entry, reason } => write!(f, "{}: {}", entry, reason),
+        }
+    }
+}
+
+impl From<toml::de::Error> for ScenarioError {
+    fn from(err: toml::de::Error) -> Self {
+        ScenarioError::Syntax(err)
+    }
+}
@@ -1,7 +1,9 @@
 use super::error::GameError;
 /// definies helper functions for the game module
 use std::cmp::Ordering;
+use std::collections::HashMap;
 
+use super::pathfinding;
 use super::unit;
 use super::unit::Unit;
 
@@ -18,17 +20,49 @@ pub fn default_unit_stats() -> unit::Stats {
 }
 
 /// Checks if requested move doesn't violate unit's stats.
-/// todo the same for the Attack state.
-pub fn assert_unit_move_within_reach(u: &Unit, (x, y): Coords) -> Result<(), GameError> {
-    let pos = &u.position;
-    let x_diff = (pos.0 as i32 - x as i32).abs() as usize;
-    let y_diff = (pos.1 as i32 - y as i32).abs() as usize;
-    if x_diff + y_diff > u.stats.movement_range {
+/// Unit has to find the route to the field costing at most range,
+/// see pathfinding::find_path for the meaning of step_cost.
+pub fn assert_unit_move_within_reach<F>(
+    u: &Unit,
+    (x, y): Coords,
+    range: usize,
+    board_size: Coords,
+    step_cost: F,
+) -> Result<(), GameError>
+where
+    F: Fn(Coords) -> Option<usize>,
+{
+    if pathfinding::find_path(u.position, (x, y), board_size, range, step_cost).is_none() {
         return Err(GameError::MoveOutsideUnitsReach(x, y));
     }
     Ok(())
 }
 
+/// Returns number of steps the unit can make in a turn.
+pub fn range_of(u: &Unit, attacking: bool) -> usize {
+    if attacking {
+        u.stats.attack_range
+    } else {
+        u.stats.movement_range
+    }
+}
+
+/// Returns number of tiles between the fields
+/// counted along the board's axes.
+pub fn distance((x1, y1): Coords, (x2, y2): Coords) -> usize {
+    let x_diff = (x1 as i64 - x2 as i64).unsigned_abs() as usize;
+    let y_diff = (y1 as i64 - y2 as i64).unsigned_abs() as usize;
+    x_diff + y_diff
+}
+
+/// Checks if the unit is free to be given orders.
+pub fn assert_unit_not_blocked(u: &Unit) -> Result<(), GameError> {
+    if let unit::State::Blocked = u.state {
+        return Err(GameError::UnitBlocked(u.id));
+    }
+    Ok(())
+}
+
 /// Given current position and the destination
 /// returns a tuple denoting next position in path.
 pub fn get_next_field_in_path((curr_x, curr_y): Coords, (dest_x, dest_y): Coords) -> Coords {
@@ -40,6 +74,56 @@ pub fn get_next_field_in_path((curr_x, curr_y): Coords, (dest_x, dest_y): Coords
     (get_next(curr_x, dest_x), get_next(curr_y, dest_y))
 }
 
+/// Returns fields around the given one that are inside the board.
+/// Units can move diagonally, so there are up to 8 of them.
+pub fn neighbours((x, y): Coords, (width, height): Coords) -> Vec<Coords> {
+    let mut fields = Vec::with_capacity(8);
+    for nx in x.saturating_sub(1)..=x + 1 {
+        for ny in y.saturating_sub(1)..=y + 1 {
+            if (nx, ny) != (x, y) && nx < width && ny < height {
+                fields.push((nx, ny));
+            }
+        }
+    }
+    fields
+}
+
+/// Checks if the unit that won its field can step on it.
+/// Follows the units standing on the way: the step is free if they
+/// end in the free field or lead back to the unit in a cycle of
+/// at least 3 units. Two units can't swap their fields.
+/// Units that don't move or lost their field block the way.
+pub fn step_is_free<F>(
+    unit_id: usize,
+    claims: &HashMap<Coords, usize>,
+    occupants: &HashMap<Coords, usize>,
+    next_field: F,
+) -> bool
+where
+    F: Fn(usize) -> Option<Coords>,
+{
+    let mut chain = vec![unit_id];
+    let mut current = unit_id;
+    loop {
+        let field = match next_field(current) {
+            Some(val) => val,
+            None => return false,
+        };
+        if claims.get(&field) != Some(&current) {
+            return false;
+        }
+        match occupants.get(&field) {
+            None => return true,
+            Some(&occupant) if occupant == unit_id => return chain.len() > 2,
+            Some(&occupant) if chain.contains(&occupant) => return false,
+            Some(&occupant) => {
+                chain.push(occupant);
+                current = occupant;
+            }
+        }
+    }
+}
+
 /// Returns copy of units relevant information.
 pub fn get_unis_moving_info(unit: &Unit) -> (unit::State, Coords) {
     (unit.state, unit.position)
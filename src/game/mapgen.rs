@@ -0,0 +1,237 @@
+/// Generates maps along with the starting armies of the players.
+/// Same seed, board size and number of players always
+/// give the same scenario, so the games can be reproduced.
+use std::collections::HashSet;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::helpers::{self, Coords};
+use super::scenario::{Deployment, Scenario, Zone};
+use super::terrain::{Map, Terrain};
+use super::unit::Category;
+use super::victory::Victory;
+
+/// Every army has at least one unit of each of the categories.
+const CATEGORIES: [Category; 3] = [Category::Cavalry, Category::Knight, Category::Pickerman];
+/// Largest number of units the player can start with.
+const MAX_ARMY: usize = 6;
+/// Number of the board's fields per single patch of terrain.
+const FIELDS_PER_PATCH: usize = 24;
+/// Largest distance from the patch's centre to its edge.
+const MAX_PATCH_RADIUS: usize = 3;
+/// Chance of the field within the patch's radius to belong to it.
+const PATCH_DENSITY: f64 = 0.75;
+
+/// Generates the scenario won by the last player standing.
+///
+/// Players start in rows spread evenly from the top to the bottom
+/// of the board, every one with the same mixed army on the same columns.
+/// Terrain is mirrored top to bottom, so the player starting in the first
+/// row faces the same board as the one starting in the last row and so on.
+/// Starting fields are plains and every army can reach all of the others.
+///
+/// Panics if there are less than 2 players or the board
+/// is narrower than 3 or lower than the number of players.
+pub fn generate(seed: u64, board_size: (usize, usize), num_of_players: u8) -> Scenario {
+    let (width, height) = board_size;
+    assert!(num_of_players > 1);
+    assert!(width >= CATEGORIES.len() && height >= num_of_players as usize);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let army = army(&mut rng, width);
+    let first_column = rng.gen_range(0..=width - army.len());
+    let mut map = terrain(&mut rng, board_size);
+    let rows = start_rows(height, num_of_players);
+    let mut zones = Vec::with_capacity(rows.len());
+    let mut units = Vec::with_capacity(rows.len() * army.len());
+    for (owner_id, &y) in (0..num_of_players).zip(rows.iter()) {
+        for (x, &category) in (first_column..).zip(army.iter()) {
+            map.set((x, y), Terrain::Plains);
+            units.push(Deployment {
+                owner_id,
+                category,
+                position: (x, y),
+            });
+        }
+        zones.push(Zone {
+            owner_id,
+            from: (first_column, y),
+            to: (first_column + army.len() - 1, y),
+        });
+    }
+    connect(&mut map, first_column, &rows);
+    Scenario {
+        num_of_players,
+        map,
+        victory: Victory::LastStanding,
+        zones,
+        units,
+    }
+}
+
+/// Picks categories of the army's units in the order they stand in.
+fn army(rng: &mut ChaCha8Rng, width: usize) -> Vec<Category> {
+    let size = rng.gen_range(CATEGORIES.len()..=MAX_ARMY.min(width));
+    let mut army = CATEGORIES.to_vec();
+    while army.len() < size {
+        army.push(*CATEGORIES.choose(rng).expect("there are categories"));
+    }
+    army.shuffle(rng);
+    army
+}
+
+/// Covers the board with patches of forest, hills, water and walls.
+/// Patches are placed in the upper half and mirrored to the lower one.
+fn terrain(rng: &mut ChaCha8Rng, (width, height): Coords) -> Map {
+    let mut map = Map::plains((width, height));
+    let patches = (width * height / FIELDS_PER_PATCH).max(1);
+    for _ in 0..patches {
+        let terrain = match rng.gen_range(0..10) {
+            0..=3 => Terrain::Forest,
+            4..=6 => Terrain::Hills,
+            7..=8 => Terrain::Water,
+            _ => Terrain::Wall,
+        };
+        let centre = (rng.gen_range(0..width), rng.gen_range(0..height.div_ceil(2)));
+        let radius = rng.gen_range(0..=MAX_PATCH_RADIUS);
+        for y in centre.1.saturating_sub(radius)..=(centre.1 + radius).min(height - 1) {
+            for x in centre.0.saturating_sub(radius)..=(centre.0 + radius).min(width - 1) {
+                if helpers::distance((x, y), centre) <= radius && rng.gen_bool(PATCH_DENSITY) {
+                    map.set((x, y), terrain);
+                    map.set((x, height - 1 - y), terrain);
+                }
+            }
+        }
+    }
+    map
+}
+
+/// Returns rows the players start in, the first one at the top
+/// and the last one at the bottom of the board.
+/// Rows of the lower half mirror the ones of the upper half.
+fn start_rows(height: usize, num_of_players: u8) -> Vec<usize> {
+    let last = num_of_players as usize - 1;
+    let mut rows: Vec<usize> = (0..=last / 2).map(|k| k * (height - 1) / last).collect();
+    for k in last / 2 + 1..=last {
+        rows.push(height - 1 - rows[last - k]);
+    }
+    rows
+}
+
+/// Makes sure the armies can reach each other by clearing
+/// the column of the first units if the terrain separates them.
+fn connect(map: &mut Map, column: usize, rows: &[usize]) {
+    let size = map.size();
+    let start = (column, rows[0]);
+    let mut reached = HashSet::new();
+    reached.insert(start);
+    let mut open = vec![start];
+    while let Some(field) = open.pop() {
+        for next in helpers::neighbours(field, size) {
+            if map.get(next).movement_cost().is_some() && reached.insert(next) {
+                open.push(next);
+            }
+        }
+    }
+    if rows.iter().all(|&y| reached.contains(&(column, y))) {
+        return;
+    }
+    for y in rows[0]..=rows[rows.len() - 1] {
+        if map.get((column, y)).movement_cost().is_none() {
+            map.set((column, y), Terrain::Plains);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::pathfinding;
+
+    #[test]
+    fn same_seed_gives_same_scenario() {
+        assert_eq!(generate(42, (30, 20), 4), generate(42, (30, 20), 4));
+        assert_ne!(generate(42, (30, 20), 4).map, generate(43, (30, 20), 4).map);
+    }
+
+    #[test]
+    fn generated_map_does_not_change_between_releases() {
+        let scenario = generate(7, (8, 6), 2);
+        assert_eq!(
+            scenario.map.to_string(),
+            "^.......\n^^^FF...\n^.......\n^.......\n^^^FF...\n^.......\n"
+        );
+        let army: Vec<(Category, Coords)> = scenario
+            .units
+            .iter()
+            .filter(|unit| unit.owner_id == 0)
+            .map(|unit| (unit.category, unit.position))
+            .collect();
+        assert_eq!(
+            army,
+            vec![
+                (Category::Knight, (2, 0)),
+                (Category::Pickerman, (3, 0)),
+                (Category::Pickerman, (4, 0)),
+                (Category::Cavalry, (5, 0)),
+                (Category::Knight, (6, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn two_players_get_mirrored_setup() {
+        let scenario = generate(5, (16, 12), 2);
+        let (width, height) = scenario.board_size();
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(scenario.map.get((x, y)), scenario.map.get((x, height - 1 - y)));
+            }
+        }
+        let (first, second): (Vec<&Deployment>, Vec<&Deployment>) =
+            scenario.units.iter().partition(|unit| unit.owner_id == 0);
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.category, b.category);
+            assert_eq!(a.position, (b.position.0, height - 1 - b.position.1));
+        }
+    }
+
+    #[test]
+    fn armies_are_mixed_and_can_reach_each_other() {
+        for seed in 0..20 {
+            let scenario = generate(seed, (20, 15), 5);
+            let map = &scenario.map;
+            let game = scenario.build();
+            assert_eq!(game.units.len(), scenario.units.len());
+            for owner in 0..5 {
+                let army: Vec<Category> = scenario
+                    .units
+                    .iter()
+                    .filter(|unit| unit.owner_id == owner)
+                    .map(|unit| unit.category)
+                    .collect();
+                assert!(CATEGORIES.iter().all(|category| army.contains(category)));
+            }
+            let from = scenario.units[0].position;
+            for unit in scenario.units.iter() {
+                let path = pathfinding::find_path(from, unit.position, map.size(), usize::MAX, |field| {
+                    map.get(field).movement_cost()
+                });
+                assert!(path.is_some(), "seed {} separates the armies", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn smallest_boards_fit_every_player() {
+        for players in 2..=8 {
+            let scenario = generate(1, (3, players as usize), players);
+            let rows: HashSet<usize> = scenario.units.iter().map(|unit| unit.position.1).collect();
+            assert_eq!(rows.len(), players as usize);
+            assert_eq!(scenario.units.len(), 3 * players as usize);
+        }
+    }
+}
@@ -1,28 +1,54 @@
 // All of this module is considered WIP
 
+pub mod battle;
 pub mod error;
 mod helpers;
+pub mod mapgen;
+mod pathfinding;
+pub mod scenario;
+pub mod terrain;
 pub mod unit;
+pub mod victory;
 
+use std::collections::BTreeMap;
 use std::collections::BinaryHeap;
+use std::collections::HashMap;
 use std::collections::HashSet;
 
+use self::battle::Battle;
 use self::error::GameError;
 use self::helpers::Coords;
+use self::terrain::Map;
 use self::unit::Unit;
+use self::victory::{GameResult, Verdict, Victory};
 
 /// Game represents current game state.
 /// See documentation for internal logic.
 pub struct Game {
     /// Num of players (active and inactive).
     num_of_players: u8,
-    /// Number of units currently in play (active).
+    /// Number of units ever added to the game.
+    /// Ids of the destroyed units are not reused.
     num_of_units: usize,
     /// Boundaries of the game board.
     board_size: (usize, usize),
+    /// Terrain of the board's fields.
+    map: Map,
     //todo Rewrite to generiational index. RustConf ECS
     /// Units currently in play (active).
     units: Vec<Unit>,
+    /// Battles fought in the turn being resolved.
+    battles: Vec<Battle>,
+    /// Condition the game is won by.
+    victory: Victory,
+    /// Number of turns resolved so far.
+    turns_played: u32,
+    /// Player holding all of the objectives
+    /// along with the number of turns it held them for.
+    objectives_held: Option<(u8, u32)>,
+    /// Players left with no units along with the turn
+    /// they were eliminated in, in order.
+    eliminated: Vec<(u8, u32)>,
 }
 
 impl Game {
@@ -31,13 +57,36 @@ impl Game {
     ///     num_of_players < 2,
     ///     board_size < (1, 1)
     pub fn new(num_of_players: u8, board_size: (usize, usize)) -> Game {
+        Game::with_victory(num_of_players, board_size, Victory::default())
+    }
+
+    /// Creates new Game won by the given condition.
+    /// Panics on the same settings as new does and
+    /// if any of the objectives lies outside the board.
+    pub fn with_victory(num_of_players: u8, board_size: (usize, usize), victory: Victory) -> Game {
+        Game::with_map(num_of_players, Map::plains(board_size), victory)
+    }
+
+    /// Creates new Game played on the given map.
+    /// Board is as big as the map.
+    pub fn with_map(num_of_players: u8, map: Map, victory: Victory) -> Game {
+        let board_size = map.size();
         assert!(board_size.0 > 0 && board_size.1 > 0);
         assert!(num_of_players > 1);
+        if let Victory::Objectives { fields, .. } = &victory {
+            assert!(fields.iter().all(|&(x, y)| x < board_size.0 && y < board_size.1));
+        }
         Game {
             num_of_players,
             num_of_units: 0,
             board_size,
+            map,
             units: Vec::new(),
+            battles: Vec::new(),
+            victory,
+            turns_played: 0,
+            objectives_held: None,
+            eliminated: Vec::new(),
         }
     }
 }
@@ -47,16 +96,17 @@ impl Game {
     /// Provides id, default stats and sets state to Idle.
     ///
     /// Panics on attempt to add unit to the noexistig player.
-    /// Returns error on attempt to add unit utside the board boundaries
-    /// or on failure when adding the unit.
-    pub fn add_unit<'a>(
-        &'a mut self,
+    /// Returns error on attempt to add unit utside the board boundaries,
+    /// on the field units can't enter or on failure when adding the unit.
+    pub fn add_unit(
+        &mut self,
         owner_id: u8,
         position: (usize, usize),
         category: unit::Category,
-    ) -> Result<&'a Unit, GameError> {
+    ) -> Result<&Unit, GameError> {
         assert!(owner_id < self.num_of_players);
         self.assert_position_in_board(position)?;
+        self.assert_position_passable(position)?;
         self.units.push(Unit {
             id: self.num_of_units,
             owner_id,
@@ -75,12 +125,26 @@ impl Game {
     /// Checks if given position is inside the currents board boundaries.
     /// If true return Ok(()). PositionOutsideTheBoard otherwise.
     fn assert_position_in_board(&self, (x, y): Coords) -> Result<(), GameError> {
-        if (x, y) >= self.board_size {
+        if x >= self.board_size.0 || y >= self.board_size.1 {
             return Err(GameError::PositionOutsideTheBoard(x, y));
         }
         Ok(())
     }
 
+    /// Checks if units can enter the field.
+    /// If true return Ok(()). ImpassableTerrain otherwise.
+    fn assert_position_passable(&self, (x, y): Coords) -> Result<(), GameError> {
+        if self.map.get((x, y)).movement_cost().is_none() {
+            return Err(GameError::ImpassableTerrain(x, y));
+        }
+        Ok(())
+    }
+
+    /// Returns terrain of the board.
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
     /// Given unit id returns reference to it.
     /// If there is no unit with the given id returns NonExistingUnit
     pub fn get_unit(&self, unit_id: usize) -> Result<&Unit, GameError> {
@@ -146,16 +210,40 @@ impl Game {
         Ok(units)
     }
 
+    /// Returns all of the units currently in play.
+    pub fn units(&self) -> &[Unit] {
+        &self.units
+    }
+
     /// After movement assertions changes unit state
     /// to Moving at given postion.
     pub fn move_unit(&mut self, unit_id: usize, (x, y): Coords) -> Result<(), GameError> {
         self.assert_position_in_board((x, y))?;
-        let unit = self.get_unit_mut(unit_id)?;
-        helpers::assert_unit_move_within_reach(&unit, (x, y))?;
-        unit.state = unit::State::Moving(x, y);
+        self.assert_position_passable((x, y))?;
+        self.assert_unit_can_reach(unit_id, (x, y), false)?;
+        self.get_unit_mut(unit_id)?.state = unit::State::Moving(x, y);
         Ok(())
     }
 
+    /// Checks if the unit is free to be given orders
+    /// and has the route to the field within its range.
+    fn assert_unit_can_reach(
+        &self,
+        unit_id: usize,
+        field: Coords,
+        attacking: bool,
+    ) -> Result<(), GameError> {
+        let unit = self.get_unit(unit_id)?;
+        helpers::assert_unit_not_blocked(unit)?;
+        helpers::assert_unit_move_within_reach(
+            unit,
+            field,
+            helpers::range_of(unit, attacking),
+            self.board_size,
+            |step| self.step_cost(unit, step, field, attacking),
+        )
+    }
+
     /// Gets both units. Sets their state as attack and the position as
     /// average of both of their positions.
     /// If there was an error, no change will be made in both of the units.
@@ -187,6 +275,18 @@ impl Game {
         }
     }
 
+    /// Sets the unit to attack the position of the target unit.
+    /// Orders of the target stay as they were.
+    /// Target hidden from the unit's owner is treated as non existing.
+    pub fn attack_unit(&mut self, unit_id: usize, target_id: usize) -> Result<(), GameError> {
+        let owner_id = self.get_unit(unit_id)?.owner_id;
+        let position = match self.get_unit(target_id) {
+            Ok(target) if self.is_visible(owner_id, target.position) => target.position,
+            _ => return Err(GameError::NonExistingUnit(target_id)),
+        };
+        self.attack_position(unit_id, position)
+    }
+
     /// After movement assertions changes unit state
     /// to Moving at given postion.
     pub fn attack_position(
@@ -195,91 +295,350 @@ impl Game {
         (x, y): (usize, usize),
     ) -> Result<(), GameError> {
         self.assert_position_in_board((x, y))?;
-        let unit = self.get_unit_mut(unit_id)?;
-        helpers::assert_unit_move_within_reach(&unit, (x, y))?;
-        unit.state = unit::State::Attack(x, y);
+        self.assert_position_passable((x, y))?;
+        self.assert_unit_can_reach(unit_id, (x, y), true)?;
+        self.get_unit_mut(unit_id)?.state = unit::State::Attack(x, y);
         Ok(())
     }
 
-    // todo test
     /// Takes all actions queued on units and executes them.
-    pub fn resolve_moves(&mut self) {
+    /// Returns battles fought on the way.
+    pub fn resolve_moves(&mut self) -> Vec<Battle> {
         let mut unresolved = self.units_to_be_moved();
-        while unresolved.len() > 0 {
+        while !unresolved.is_empty() {
             unresolved = self.make_move(unresolved);
         }
-        self.resolve_blockades()
+        self.resolve_blockades();
+        self.settle_turn();
+        self.battles.drain(..).collect()
     }
 
-    /// Returns queue of ids of the units that require moving actions.
+    /// Counts the resolved turn, the turns objectives were held for
+    /// and records players who lost their last unit.
+    fn settle_turn(&mut self) {
+        self.turns_played += 1;
+        let turn = self.turns_played;
+        for player in 0..self.num_of_players {
+            let has_units = self.units.iter().any(|unit| unit.owner_id == player);
+            if !has_units && !self.is_eliminated(player) {
+                self.eliminated.push((player, turn));
+            }
+        }
+        self.objectives_held = match (self.objectives_holder(), self.objectives_held) {
+            (Some(holder), Some((held_by, turns))) if holder == held_by => Some((holder, turns + 1)),
+            (Some(holder), _) => Some((holder, 1)),
+            (None, _) => None,
+        };
+    }
+
+    /// Returns player whose units stand on all of the objectives.
+    fn objectives_holder(&self) -> Option<u8> {
+        let fields = match &self.victory {
+            Victory::Objectives { fields, .. } => fields,
+            _ => return None,
+        };
+        let mut owners = fields
+            .iter()
+            .map(|&field| self.unit_at(field).map(|unit| unit.owner_id));
+        let holder = owners.next()??;
+        if owners.all(|owner| owner == Some(holder)) {
+            Some(holder)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the player lost all of its units.
+    fn is_eliminated(&self, player: u8) -> bool {
+        self.eliminated.iter().any(|&(eliminated, _)| eliminated == player)
+    }
+
+    /// Returns score of the player, the number of its units standing.
+    pub fn score(&self, player: u8) -> usize {
+        self.units.iter().filter(|unit| unit.owner_id == player).count()
+    }
+
+    /// Returns queue of ids of the units that require moving actions
+    /// along with the enemies they already see.
     fn units_to_be_moved(&self) -> BinaryHeap<unit::MovingWrapper> {
         self.units
             .iter()
-            .filter(|&unit| match unit.state {
-                unit::State::Moving(..) | unit::State::Attack(..) => true,
-                _ => false,
+            .filter(|&unit| {
+                matches!(
+                    unit.state,
+                    unit::State::Moving(..) | unit::State::Attack(..)
+                )
+            })
+            .map(|unit| unit::MovingWrapper {
+                sighted: self.enemies_in_vision(unit),
+                ..unit::MovingWrapper::new(unit.id)
             })
-            .map(|unit| unit::MovingWrapper::new(unit.id))
             .collect()
     }
 
-    // todo test
+    /// Returns ids of the enemy units the unit sees.
+    fn enemies_in_vision(&self, unit: &Unit) -> HashSet<usize> {
+        self.units
+            .iter()
+            .filter(|other| other.owner_id != unit.owner_id)
+            .filter(|other| self.sees(unit, other.position))
+            .map(|other| other.id)
+            .collect()
+    }
+
+    /// Checks if any of the player's units sees the field.
+    pub fn is_visible(&self, player_id: u8, field: Coords) -> bool {
+        self.units
+            .iter()
+            .filter(|unit| unit.owner_id == player_id)
+            .any(|unit| self.sees(unit, field))
+    }
+
+    /// Returns fields of the board seen by any of the player's units.
+    pub fn visible_tiles(&self, player_id: u8) -> HashSet<Coords> {
+        let (width, height) = self.board_size;
+        let mut tiles = HashSet::new();
+        for unit in self.units.iter().filter(|unit| unit.owner_id == player_id) {
+            let (x, y) = unit.position;
+            let range = self.vision_of(unit);
+            for vx in x.saturating_sub(range)..=(x + range).min(width - 1) {
+                for vy in y.saturating_sub(range)..=(y + range).min(height - 1) {
+                    if self.sees(unit, (vx, vy)) {
+                        tiles.insert((vx, vy));
+                    }
+                }
+            }
+        }
+        tiles
+    }
+
+    /// Returns units the player can see, its own ones included.
+    pub fn visible_units(&self, player_id: u8) -> Vec<&Unit> {
+        self.units
+            .iter()
+            .filter(|unit| self.is_visible(player_id, unit.position))
+            .collect()
+    }
+
     /// Makes a single move for each unit in units argument.
+    /// All of the units step at once, so chains of units following
+    /// each other and cycles of units moving into each other's fields
+    /// move together. Two units swapping their fields collide though.
+    /// If more units want the same field the one with the lowest id gets it.
+    /// Units that can't move wait as long as the others make progress,
+    /// once nobody can move they stop.
     /// Returns filtered queue. With units that still need to be moved.
     fn make_move(
         &mut self,
         mut units: BinaryHeap<unit::MovingWrapper>,
     ) -> BinaryHeap<unit::MovingWrapper> {
+        let mut wrappers: Vec<unit::MovingWrapper> = units.drain().collect();
+        wrappers.sort_by_key(|wrapper| wrapper.unit_id);
+        let mut steps = BTreeMap::new();
+        for wrapper in wrappers {
+            if let Some(next_pos) = self.plan_step(&wrapper) {
+                steps.insert(wrapper.unit_id, (wrapper, next_pos));
+            }
+        }
+        // Battles could have destroyed the units planned earlier.
+        steps.retain(|&unit_id, _| self.get_unit(unit_id).is_ok());
+
+        let mut claims: HashMap<Coords, usize> = HashMap::new();
+        for (&unit_id, &(_, next_pos)) in steps.iter() {
+            claims.entry(next_pos).or_insert(unit_id);
+        }
+        let occupants: HashMap<Coords, usize> =
+            self.units.iter().map(|u| (u.position, u.id)).collect();
+        let movers: HashSet<usize> = claims
+            .values()
+            .cloned()
+            .filter(|&unit_id| {
+                helpers::step_is_free(unit_id, &claims, &occupants, |id| {
+                    steps.get(&id).map(|&(_, next_pos)| next_pos)
+                })
+            })
+            .collect();
+
         let mut filtered = BinaryHeap::new();
-        for u in units.drain() {
-            if let Some(val) = self.resolve_unit(&u) {
+        for (unit_id, (wrapper, next_pos)) in steps {
+            if movers.is_empty() {
+                self.get_unit_mut(unit_id).unwrap().state = unit::State::Idle;
+            } else if !movers.contains(&unit_id) {
+                filtered.push(wrapper);
+            } else if let Some(val) = self.resolve_unit(&wrapper, next_pos) {
                 filtered.push(val);
             }
         }
         filtered
     }
 
+    /// Returns field the unit wants to step on next.
+    /// Route is found anew on every step, so units go around
+    /// the ones that stopped on their way, within the part
+    /// of the range they haven't spent yet.
+    /// Attacking unit fights the enemy standing on it first.
+    /// Returns None if the unit doesn't move anymore,
+    /// either because it arrived or there is no route within its range.
+    fn plan_step(&mut self, wrapper: &unit::MovingWrapper) -> Option<Coords> {
+        // Unit could have been destroyed in the battle since it was queued.
+        let unit = self.get_unit(wrapper.unit_id).ok()?;
+        let (state, pos) = helpers::get_unis_moving_info(unit);
+        let owner_id = unit.owner_id;
+        let (dest, attacking) = match state {
+            unit::State::Moving(x, y) => ((x, y), false),
+            unit::State::Attack(x, y) => ((x, y), true),
+            _ => return None,
+        };
+        let route = pathfinding::find_path(
+            pos,
+            dest,
+            self.board_size,
+            helpers::range_of(unit, attacking).saturating_sub(wrapper.cost_spent),
+            |field| self.step_cost(unit, field, dest, attacking),
+        );
+        let next_pos = match route.as_ref().and_then(|fields| fields.first()) {
+            Some(&val) => val,
+            None => {
+                self.get_unit_mut(wrapper.unit_id).unwrap().state = unit::State::Idle;
+                return None;
+            }
+        };
+        let enemy = self
+            .unit_at(next_pos)
+            .filter(|other| attacking && other.owner_id != owner_id)
+            .map(|other| other.id);
+        if let Some(defender_id) = enemy {
+            match self.battle(wrapper.unit_id, defender_id, next_pos) {
+                battle::Outcome::AttackerWon => (),
+                battle::Outcome::DefenderWon => return None,
+                battle::Outcome::Draw => {
+                    self.get_unit_mut(wrapper.unit_id).unwrap().state = unit::State::Idle;
+                    return None;
+                }
+            }
+        }
+        Some(next_pos)
+    }
+
     /// Moves unit to it's next position and returns its updated MovingWrapper.
-    /// If the move was completed or the unit entered a blockade resolve_unit
-    /// changes units state approprietly.
-    fn resolve_unit(&mut self, wrapper: &unit::MovingWrapper) -> Option<unit::MovingWrapper> {
+    /// If the move was completed resolve_unit changes units state approprietly.
+    /// Moving unit halts once it sights the enemy it didn't see before.
+    /// Field has to be free or vacated in the same step, see make_move.
+    fn resolve_unit(
+        &mut self,
+        wrapper: &unit::MovingWrapper,
+        next_pos: Coords,
+    ) -> Option<unit::MovingWrapper> {
         // Because of rusts weird pattern matching it has to be done that way
-        let (state, pos) = helpers::get_unis_moving_info(self.get_unit(wrapper.unit_id).unwrap());
-
-        match state {
-            unit::State::Moving(x, y) => {
-                let next_pos = helpers::get_next_field_in_path(pos, (x, y));
-                if self.field_empty(next_pos) {
-                    let u = self.get_unit_mut(wrapper.unit_id).unwrap();
-                    u.position = next_pos;
-                    if u.position == (x, y) {
-                        u.state = unit::State::Idle;
-                        return None;
-                    }
-                    // todo if enemy unit in vision change state to idle
-                    return Some(unit::MovingWrapper {
-                        moves_made: wrapper.moves_made + 1,
-                        unit_id: wrapper.unit_id,
-                    });
-                } else {
-                    // todo <- resolve it somehow (?)
-                    // but how do we resolve situations as
-                    //     a <- b
-                    //     v    ^
-                    //     c -> d
-                    // where we cant move any unit at all
-                    let u = self.get_unit_mut(wrapper.unit_id).unwrap();
-                    // for now let's just stop moving.
-                    // maybe later make max number of repetitions to resolve turn ?
-                    u.state = unit::State::Idle;
-                }
+        let (state, _) = match self.get_unit(wrapper.unit_id) {
+            Ok(unit) => helpers::get_unis_moving_info(unit),
+            Err(_) => return None,
+        };
+        let (x, y) = match state {
+            unit::State::Moving(x, y) | unit::State::Attack(x, y) => (x, y),
+            _ => return None,
+        };
+        let cost = self.map.get(next_pos).movement_cost().unwrap_or(1).max(1);
+        let u = self.get_unit_mut(wrapper.unit_id).unwrap();
+        u.position = next_pos;
+        if u.position == (x, y) {
+            u.state = unit::State::Idle;
+            return None;
+        }
+        if let unit::State::Moving(..) = state {
+            let unit = self.get_unit(wrapper.unit_id).unwrap();
+            let sighted = self.enemies_in_vision(unit);
+            if !sighted.is_subset(&wrapper.sighted) {
+                self.get_unit_mut(wrapper.unit_id).unwrap().state = unit::State::Idle;
+                return None;
+            }
+        }
+        Some(unit::MovingWrapper {
+            moves_made: wrapper.moves_made + 1,
+            cost_spent: wrapper.cost_spent + cost,
+            unit_id: wrapper.unit_id,
+            sighted: wrapper.sighted.clone(),
+        })
+    }
+
+    /// Returns cost of entering the field on the unit's route to the destination.
+    /// None if the unit can't go through it.
+    /// Besides the terrain, units that don't move this turn block the way,
+    /// unless they stand at the destination.
+    /// Attacking unit runs into the enemy ones though.
+    fn step_cost(&self, unit: &Unit, field: Coords, dest: Coords, attacking: bool) -> Option<usize> {
+        let cost = self.map.get(field).movement_cost()?;
+        let passable = match self.unit_at(field) {
+            None => true,
+            Some(_) if field == dest => true,
+            Some(other) if other.id == unit.id => true,
+            Some(other) if attacking && other.owner_id != unit.owner_id => true,
+            Some(other) => matches!(
+                other.state,
+                unit::State::Moving(..) | unit::State::Attack(..)
+            ),
+        };
+        if passable {
+            Some(cost)
+        } else {
+            None
+        }
+    }
+
+    /// Returns vision range of the unit changed by the terrain it stands on.
+    fn vision_of(&self, unit: &Unit) -> usize {
+        let modifier = self.map.get(unit.position).vision_modifier();
+        (unit.stats.vision_range as isize + modifier).max(0) as usize
+    }
+
+    /// Checks if the unit sees the field.
+    fn sees(&self, unit: &Unit, field: Coords) -> bool {
+        helpers::distance(unit.position, field) <= self.vision_of(unit)
+    }
+
+    /// Returns unit occupying the field.
+    fn unit_at(&self, position: Coords) -> Option<&Unit> {
+        self.units.iter().find(|unit| unit.position == position)
+    }
+
+    /// Fights the battle between the attacker and the defender
+    /// occupying the given field. Category advantage decides the outcome,
+    /// defense bonus of the field's terrain turns it in the defender's favour:
+    /// the attacker's win becomes a draw and the draw becomes its loss.
+    /// Loser is removed from the game and the battle is recorded.
+    fn battle(
+        &mut self,
+        attacker_id: usize,
+        defender_id: usize,
+        position: Coords,
+    ) -> battle::Outcome {
+        let attacker = self.get_unit(attacker_id).unwrap().category;
+        let defender = self.get_unit(defender_id).unwrap().category;
+        let advantage = if attacker.beats(defender) {
+            1
+        } else if defender.beats(attacker) {
+            -1
+        } else {
+            0
+        };
+        let outcome = match advantage - self.map.get(position).defense_bonus() {
+            val if val > 0 => {
+                self.units.retain(|unit| unit.id != defender_id);
+                battle::Outcome::AttackerWon
             }
-            unit::State::Attack(_x, _y) => {
-                // todo implement
+            val if val < 0 => {
+                self.units.retain(|unit| unit.id != attacker_id);
+                battle::Outcome::DefenderWon
             }
-            _ => {}
+            _ => battle::Outcome::Draw,
         };
-        None
+        self.battles.push(Battle {
+            attacker_id,
+            defender_id,
+            position,
+            outcome,
+        });
+        outcome
     }
 
     /// Checks whether board field is not occupied by
@@ -293,17 +652,83 @@ impl Game {
         true
     }
 
-    // todo test, doc
+    /// Marks units that can't leave their field because of the enemy
+    /// as Blocked and releases the ones whose blockade was broken.
+    /// Unit is blocked when every neighbouring field is either outside
+    /// the board or occupied, and at least one of them by the enemy.
     fn resolve_blockades(&mut self) {
-        // todo body
+        let blocked: HashSet<usize> = self
+            .units
+            .iter()
+            .filter(|unit| self.is_blocked(unit))
+            .map(|unit| unit.id)
+            .collect();
+        for unit in self.units.iter_mut() {
+            if blocked.contains(&unit.id) {
+                unit.state = unit::State::Blocked;
+            } else if let unit::State::Blocked = unit.state {
+                unit.state = unit::State::Idle;
+            }
+        }
+    }
+
+    /// Checks if the unit is blocked in by the enemy.
+    fn is_blocked(&self, unit: &Unit) -> bool {
+        let fields = helpers::neighbours(unit.position, self.board_size);
+        let free = |field: Coords| {
+            self.field_empty(field) && self.map.get(field).movement_cost().is_some()
+        };
+        if fields.iter().any(|&field| free(field)) {
+            return false;
+        }
+        fields
+            .iter()
+            .filter_map(|&field| self.unit_at(field))
+            .any(|other| other.owner_id != unit.owner_id)
+    }
+
+    /// Checks the victory condition after the last resolved turn.
+    /// Returns players eliminated in that turn
+    /// and the result if the game is over.
+    pub fn game_over(&self) -> Verdict {
+        let eliminated = self
+            .eliminated
+            .iter()
+            .filter(|&&(_, turn)| turn == self.turns_played)
+            .map(|&(player, _)| player)
+            .collect();
+        Verdict {
+            eliminated,
+            result: self.result(),
+        }
     }
 
-    // todo test
-    /// If the game os over returns Ok with the id of the
-    /// player who won. None otherwise.
-    pub fn game_over(&self) -> Option<usize> {
-        // todo implement
-        None
+    /// Decides the result of the game, if it is over.
+    fn result(&self) -> Option<GameResult> {
+        let standing: Vec<u8> = (0..self.num_of_players)
+            .filter(|&player| !self.is_eliminated(player))
+            .collect();
+        match standing.len() {
+            0 => return Some(GameResult::Draw),
+            1 => return Some(GameResult::Won(standing[0])),
+            _ => (),
+        }
+        match &self.victory {
+            Victory::LastStanding => None,
+            Victory::Objectives { turns, .. } => match self.objectives_held {
+                Some((holder, held)) if held >= *turns => Some(GameResult::Won(holder)),
+                _ => None,
+            },
+            Victory::TurnLimit(limit) if self.turns_played >= *limit => {
+                let best = standing.iter().map(|&p| self.score(p)).max()?;
+                let mut leaders = standing.iter().filter(|&&p| self.score(p) == best);
+                match (leaders.next(), leaders.next()) {
+                    (Some(&leader), None) => Some(GameResult::Won(leader)),
+                    _ => Some(GameResult::Draw),
+                }
+            }
+            Victory::TurnLimit(_) => None,
+        }
     }
 }
 
@@ -323,7 +748,7 @@ mod tests {
         }
     }
 
-    #[allow(dead_code)]
+    #[allow(unused_macros)]
     macro_rules! assert_match_debug {
         ($e:expr, $( $p:pat )+) => {
             assert!(match $e {
@@ -498,13 +923,13 @@ mod tests {
         g.add_unit(0, (5, 1), unit::Category::Pickerman).unwrap();
         let units = g.get_units(vec![0, 3, 6, 7, 8]);
         // todo test it some more <- result changed after implementation change
-        assert_match!(units, Err(GameError::NonExistingUnit(6...8)));
+        assert_match!(units, Err(GameError::NonExistingUnit(6..=8)));
     }
 
     #[test]
     fn get_noexisting_unit() {
         let g = Game::new(2, (5, 5));
-        assert_match!(g.get_unit(0), Err(GameError::NonExistingUnit(0)))
+        assert_match!(g.get_unit(0), Err(GameError::NonExistingUnit(0)));
     }
 
     #[test]
@@ -514,11 +939,7 @@ mod tests {
         assert!(match g.move_unit(0, (4, 4)) {
             Ok(_) => {
                 let u = g.get_unit(0).unwrap();
-                if let unit::State::Moving(4, 4) = u.state {
-                    true
-                } else {
-                    false
-                }
+                matches!(u.state, unit::State::Moving(4, 4))
             }
             Err(_) => false,
         });
@@ -538,11 +959,7 @@ mod tests {
         assert!(match g.attack_position(0, (4, 4)) {
             Ok(_) => {
                 let u = g.get_unit(0).unwrap();
-                if let unit::State::Attack(4, 4) = u.state {
-                    true
-                } else {
-                    false
-                }
+                matches!(u.state, unit::State::Attack(4, 4))
             }
             Err(_) => false,
         });
@@ -552,7 +969,7 @@ mod tests {
     fn attack_position_outside_boundaries() {
         let mut g = Game::new(2, (10, 10));
         g.add_unit(0, (2, 2), unit::Category::Cavalry).unwrap();
-        assert_match!(g.attack_position(0, (11, 10)), Err(_))
+        assert_match!(g.attack_position(0, (11, 10)), Err(_));
     }
 
     #[test]
@@ -595,6 +1012,27 @@ mod tests {
         });
     }
 
+    #[test]
+    fn attack_unit_leaves_target_orders() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (2, 2), unit::Category::Cavalry).unwrap();
+        g.move_unit(1, (2, 4)).unwrap();
+        assert_match!(g.attack_unit(0, 1), Ok(()));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Attack(2, 2));
+        assert_match!(g.get_unit(1).unwrap().state, unit::State::Moving(2, 4));
+        assert_match!(g.attack_unit(0, 7), Err(GameError::NonExistingUnit(7)));
+    }
+
+    #[test]
+    fn hidden_target_is_not_attacked() {
+        let mut g = Game::new(2, (100, 100));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (99, 99), unit::Category::Knight).unwrap();
+        assert_match!(g.attack_unit(0, 1), Err(GameError::NonExistingUnit(1)));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
     #[test]
     fn battle_non_existing_unit_second() {
         let mut g = Game::new(2, (10, 10));
@@ -644,7 +1082,7 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.add_unit(0, (1, 2), unit::Category::Knight).unwrap();
         g.add_unit(0, (1, 3), unit::Category::Knight).unwrap();
-        assert!(g.units_to_be_moved().len() == 0);
+        assert!(g.units_to_be_moved().is_empty());
     }
 
     #[test]
@@ -703,8 +1141,10 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.move_unit(0, (3, 3)).unwrap();
         let mut wrap = unit::MovingWrapper::new(0);
-        wrap = g.resolve_unit(&wrap).unwrap();
+        let next_pos = g.plan_step(&wrap).unwrap();
+        wrap = g.resolve_unit(&wrap, next_pos).unwrap();
         assert!(wrap.moves_made == 1);
+        assert!(wrap.cost_spent == 1);
         assert!(wrap.unit_id == 0);
     }
 
@@ -714,7 +1154,8 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.move_unit(0, (1, 3)).unwrap();
         let mut wrap = unit::MovingWrapper::new(0);
-        wrap = g.resolve_unit(&wrap).unwrap();
+        let next_pos = g.plan_step(&wrap).unwrap();
+        wrap = g.resolve_unit(&wrap, next_pos).unwrap();
         let u = g.get_unit(wrap.unit_id).unwrap();
         assert!(u.position == (1, 2));
     }
@@ -725,7 +1166,8 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.move_unit(0, (3, 1)).unwrap();
         let mut wrap = unit::MovingWrapper::new(0);
-        wrap = g.resolve_unit(&wrap).unwrap();
+        let next_pos = g.plan_step(&wrap).unwrap();
+        wrap = g.resolve_unit(&wrap, next_pos).unwrap();
         let u = g.get_unit(wrap.unit_id).unwrap();
         assert!(u.position == (2, 1));
     }
@@ -736,7 +1178,8 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.move_unit(0, (3, 3)).unwrap();
         let mut wrap = unit::MovingWrapper::new(0);
-        wrap = g.resolve_unit(&wrap).unwrap();
+        let next_pos = g.plan_step(&wrap).unwrap();
+        wrap = g.resolve_unit(&wrap, next_pos).unwrap();
         let u = g.get_unit(wrap.unit_id).unwrap();
         assert!(u.position == (2, 2));
     }
@@ -747,7 +1190,8 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.move_unit(0, (2, 2)).unwrap();
         let wrap = unit::MovingWrapper::new(0);
-        g.resolve_unit(&wrap);
+        let next_pos = g.plan_step(&wrap).unwrap();
+        g.resolve_unit(&wrap, next_pos);
         let u = g.get_unit(0).unwrap();
         assert_match!(u.state, unit::State::Idle);
     }
@@ -758,6 +1202,577 @@ mod tests {
         g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
         g.move_unit(0, (2, 2)).unwrap();
         let wrap = unit::MovingWrapper::new(0);
-        assert_match!(g.resolve_unit(&wrap), None);
+        let next_pos = g.plan_step(&wrap).unwrap();
+        assert_match!(g.resolve_unit(&wrap, next_pos), None);
+    }
+
+    #[test]
+    fn category_triangle() {
+        use unit::Category::*;
+        assert!(Cavalry.beats(Knight));
+        assert!(Knight.beats(Pickerman));
+        assert!(Pickerman.beats(Cavalry));
+        assert!(!Knight.beats(Cavalry));
+        assert!(!Cavalry.beats(Cavalry));
+    }
+
+    #[test]
+    fn attacker_with_advantage_destroys_defender_and_moves_on() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Cavalry).unwrap();
+        g.add_unit(1, (2, 0), unit::Category::Knight).unwrap();
+        g.attack_position(0, (3, 0)).unwrap();
+        let battles = g.resolve_moves();
+        assert_eq!(
+            battles,
+            vec![Battle {
+                attacker_id: 0,
+                defender_id: 1,
+                position: (2, 0),
+                outcome: battle::Outcome::AttackerWon,
+            }]
+        );
+        assert_match!(g.get_unit(1), Err(GameError::NonExistingUnit(1)));
+        assert_match!(
+            g.get_unit(0).unwrap(),
+            Unit {
+                position: (3, 0),
+                state: unit::State::Idle,
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn attacker_without_advantage_is_destroyed() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (2, 0), unit::Category::Cavalry).unwrap();
+        g.attack_position(0, (3, 0)).unwrap();
+        let battles = g.resolve_moves();
+        assert_eq!(battles.len(), 1);
+        assert_eq!(battles[0].outcome, battle::Outcome::DefenderWon);
+        assert_match!(g.get_unit(0), Err(GameError::NonExistingUnit(0)));
+        assert_eq!(g.get_unit(1).unwrap().position, (2, 0));
+    }
+
+    #[test]
+    fn attacker_of_the_same_category_is_repelled() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (2, 0), unit::Category::Knight).unwrap();
+        g.attack_position(0, (3, 0)).unwrap();
+        let battles = g.resolve_moves();
+        assert_eq!(battles[0].outcome, battle::Outcome::Draw);
+        assert_eq!(g.units().len(), 2);
+        assert_match!(
+            g.get_unit(0).unwrap(),
+            Unit {
+                position: (1, 0),
+                state: unit::State::Idle,
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn own_units_are_not_attacked() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Cavalry).unwrap();
+        g.add_unit(0, (2, 0), unit::Category::Knight).unwrap();
+        g.attack_position(0, (3, 0)).unwrap();
+        assert!(g.resolve_moves().is_empty());
+        assert_eq!(g.units().len(), 2);
+        assert_eq!(g.get_unit(0).unwrap().position, (3, 0));
+        assert_eq!(g.get_unit(1).unwrap().position, (2, 0));
+    }
+
+    #[test]
+    fn moving_unit_does_not_start_battle() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Cavalry).unwrap();
+        g.add_unit(1, (2, 0), unit::Category::Knight).unwrap();
+        g.move_unit(0, (3, 0)).unwrap();
+        assert!(g.resolve_moves().is_empty());
+        assert_eq!(g.units().len(), 2);
+    }
+
+    #[test]
+    fn destroyed_unit_ids_are_not_reused() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Cavalry).unwrap();
+        g.add_unit(1, (1, 0), unit::Category::Knight).unwrap();
+        g.attack_position(0, (1, 0)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.add_unit(1, (5, 5), unit::Category::Knight).unwrap().id, 2);
+    }
+
+    /// Surrounds the unit standing at (1, 1) with the units of the given owners.
+    fn surround(g: &mut Game, owners: [u8; 8]) {
+        let fields = helpers::neighbours((1, 1), g.board_size);
+        for (&field, &owner) in fields.iter().zip(owners.iter()) {
+            g.add_unit(owner, field, unit::Category::Knight).unwrap();
+        }
+    }
+
+    #[test]
+    fn unit_surrounded_by_the_enemy_is_blocked() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
+        surround(&mut g, [0, 0, 0, 0, 0, 0, 0, 1]);
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Blocked);
+    }
+
+    #[test]
+    fn unit_pinned_to_the_edge_is_blocked() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (1, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (0, 1), unit::Category::Knight).unwrap();
+        g.add_unit(1, (1, 1), unit::Category::Knight).unwrap();
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Blocked);
+    }
+
+    #[test]
+    fn unit_surrounded_by_own_units_is_not_blocked() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
+        surround(&mut g, [0; 8]);
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn unit_with_free_field_is_not_blocked() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
+        let fields = helpers::neighbours((1, 1), g.board_size);
+        for &field in fields.iter().skip(1) {
+            g.add_unit(1, field, unit::Category::Knight).unwrap();
+        }
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn blocked_unit_is_not_moved_nor_ordered() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
+        surround(&mut g, [1; 8]);
+        g.resolve_moves();
+        assert_match!(g.move_unit(0, (5, 5)), Err(GameError::UnitBlocked(0)));
+        assert_match!(
+            g.attack_position(0, (2, 2)),
+            Err(GameError::UnitBlocked(0))
+        );
+        assert!(g.units_to_be_moved().is_empty());
+    }
+
+    #[test]
+    fn blockade_is_released_once_broken() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (1, 1), unit::Category::Knight).unwrap();
+        surround(&mut g, [1; 8]);
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Blocked);
+        // Unit 8 stands at (2, 2) and leaves the field free.
+        g.move_unit(8, (5, 5)).unwrap();
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+        g.move_unit(0, (2, 2)).unwrap();
+    }
+
+    #[test]
+    fn blockade_is_released_once_the_enemy_is_destroyed() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(0, (1, 0), unit::Category::Knight).unwrap();
+        g.add_unit(0, (0, 1), unit::Category::Knight).unwrap();
+        g.add_unit(1, (1, 1), unit::Category::Knight).unwrap();
+        g.add_unit(0, (3, 3), unit::Category::Cavalry).unwrap();
+        g.resolve_moves();
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Blocked);
+        g.attack_position(4, (1, 1)).unwrap();
+        g.resolve_moves();
+        assert_match!(g.get_unit(3), Err(GameError::NonExistingUnit(3)));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn units_in_line_move_together() {
+        let mut g = Game::new(2, (10, 10));
+        for x in 0..3 {
+            g.add_unit(0, (x, 0), unit::Category::Knight).unwrap();
+            g.move_unit(x, (x + 1, 0)).unwrap();
+        }
+        g.resolve_moves();
+        for x in 0..3 {
+            assert_eq!(g.get_unit(x).unwrap().position, (x + 1, 0));
+        }
+    }
+
+    #[test]
+    fn units_moving_in_cycle_rotate() {
+        //     a <- b
+        //     v    ^
+        //     c -> d
+        let mut g = Game::new(2, (10, 10));
+        let fields = [(0, 0), (0, 1), (1, 1), (1, 0)];
+        for (id, &field) in fields.iter().enumerate() {
+            g.add_unit(0, field, unit::Category::Knight).unwrap();
+            g.move_unit(id, fields[(id + 1) % 4]).unwrap();
+        }
+        g.resolve_moves();
+        for (id, _) in fields.iter().enumerate() {
+            assert_match!(
+                g.get_unit(id).unwrap(),
+                Unit {
+                    state: unit::State::Idle,
+                    ..
+                }
+            );
+            assert_eq!(g.get_unit(id).unwrap().position, fields[(id + 1) % 4]);
+        }
+    }
+
+    #[test]
+    fn units_swapping_fields_collide() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(0, (1, 0), unit::Category::Knight).unwrap();
+        g.move_unit(0, (1, 0)).unwrap();
+        g.move_unit(1, (0, 0)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (0, 0));
+        assert_eq!(g.get_unit(1).unwrap().position, (1, 0));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+        assert_match!(g.get_unit(1).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn unit_with_lower_id_wins_contested_field() {
+        for &first in &[0, 1] {
+            let mut g = Game::new(2, (10, 10));
+            g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+            g.add_unit(1, (2, 0), unit::Category::Knight).unwrap();
+            // Order in which the orders were given doesn't matter.
+            g.move_unit(first, (1, 0)).unwrap();
+            g.move_unit(1 - first, (1, 0)).unwrap();
+            g.resolve_moves();
+            assert_eq!(g.get_unit(0).unwrap().position, (1, 0));
+            assert_eq!(g.get_unit(1).unwrap().position, (2, 0));
+            assert_match!(g.get_unit(1).unwrap().state, unit::State::Idle);
+        }
+    }
+
+    #[test]
+    fn unit_that_lost_contested_field_follows_the_winner() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 1), unit::Category::Knight).unwrap();
+        g.add_unit(0, (1, 0), unit::Category::Knight).unwrap();
+        g.move_unit(0, (3, 1)).unwrap();
+        g.move_unit(1, (1, 3)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (3, 1));
+        assert_eq!(g.get_unit(1).unwrap().position, (1, 3));
+    }
+
+    #[test]
+    fn unit_goes_around_standing_unit() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(0, (2, 0), unit::Category::Knight).unwrap();
+        g.move_unit(0, (3, 0)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (3, 0));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn rerouted_unit_stops_once_its_range_is_spent() {
+        let map = terrain::Map::parse("............\n~~~F~~~~~~~~\n").unwrap();
+        let mut g = Game::with_map(2, map, Victory::default());
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(0, (3, 1), unit::Category::Knight).unwrap();
+        g.move_unit(1, (3, 0)).unwrap();
+        g.move_unit(0, (10, 0)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(1).unwrap().position, (3, 0));
+        assert_eq!(g.get_unit(0).unwrap().position, (1, 0));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn unit_walled_in_by_standing_units_stops() {
+        let mut g = Game::new(2, (10, 3));
+        g.add_unit(0, (0, 1), unit::Category::Knight).unwrap();
+        for y in 0..3 {
+            g.add_unit(0, (2, y), unit::Category::Knight).unwrap();
+        }
+        assert_match!(
+            g.move_unit(0, (4, 1)),
+            Err(GameError::MoveOutsideUnitsReach(4, 1))
+        );
+        g.move_unit(0, (1, 1)).unwrap();
+        g.get_unit_mut(0).unwrap().state = unit::State::Moving(4, 1);
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (0, 1));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn move_reach_is_measured_along_the_route() {
+        let mut g = Game::new(2, (20, 20));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.move_unit(0, (10, 10)).unwrap();
+        for y in 0..10 {
+            g.add_unit(1, (1, y), unit::Category::Knight).unwrap();
+        }
+        assert_match!(
+            g.move_unit(0, (2, 0)),
+            Err(GameError::MoveOutsideUnitsReach(2, 0))
+        );
+    }
+
+    #[test]
+    fn game_goes_on_while_players_have_units() {
+        let mut g = Game::new(2, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (5, 5), unit::Category::Knight).unwrap();
+        assert_eq!(g.game_over(), Verdict::default());
+        g.resolve_moves();
+        assert_eq!(g.game_over(), Verdict::default());
+    }
+
+    #[test]
+    fn last_player_standing_wins() {
+        let mut g = Game::new(3, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Cavalry).unwrap();
+        g.add_unit(1, (2, 0), unit::Category::Knight).unwrap();
+        g.add_unit(2, (0, 5), unit::Category::Knight).unwrap();
+        g.attack_position(0, (2, 0)).unwrap();
+        g.resolve_moves();
+        let verdict = g.game_over();
+        assert_eq!(verdict.eliminated, vec![1]);
+        assert_eq!(verdict.result, None);
+        g.attack_position(0, (0, 5)).unwrap();
+        g.resolve_moves();
+        let verdict = g.game_over();
+        assert_eq!(verdict.eliminated, vec![2]);
+        assert_eq!(verdict.result, Some(GameResult::Won(0)));
+    }
+
+    #[test]
+    fn eliminated_player_is_reported_once() {
+        let mut g = Game::new(3, (10, 10));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (5, 5), unit::Category::Knight).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.game_over().eliminated, vec![2]);
+        g.resolve_moves();
+        assert!(g.game_over().eliminated.is_empty());
+    }
+
+    #[test]
+    fn holding_objectives_for_given_turns_wins() {
+        let victory = Victory::Objectives {
+            fields: vec![(5, 5), (5, 6)],
+            turns: 2,
+        };
+        let mut g = Game::with_victory(2, (10, 10), victory);
+        g.add_unit(0, (5, 4), unit::Category::Knight).unwrap();
+        g.add_unit(0, (5, 6), unit::Category::Knight).unwrap();
+        g.add_unit(1, (0, 0), unit::Category::Knight).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, None);
+        g.move_unit(0, (5, 5)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, None);
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, Some(GameResult::Won(0)));
+    }
+
+    #[test]
+    fn leaving_objective_resets_the_count() {
+        let victory = Victory::Objectives {
+            fields: vec![(5, 5)],
+            turns: 2,
+        };
+        let mut g = Game::with_victory(2, (10, 10), victory);
+        g.add_unit(0, (5, 5), unit::Category::Knight).unwrap();
+        g.add_unit(1, (0, 0), unit::Category::Knight).unwrap();
+        g.resolve_moves();
+        g.move_unit(0, (5, 7)).unwrap();
+        g.resolve_moves();
+        g.move_unit(0, (5, 5)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, None);
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, Some(GameResult::Won(0)));
+    }
+
+    #[test]
+    fn highest_score_wins_at_turn_limit() {
+        let mut g = Game::with_victory(2, (10, 10), Victory::TurnLimit(2));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (5, 5), unit::Category::Knight).unwrap();
+        g.add_unit(1, (6, 6), unit::Category::Knight).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, None);
+        g.resolve_moves();
+        assert_eq!(g.score(1), 2);
+        assert_eq!(g.game_over().result, Some(GameResult::Won(1)));
+    }
+
+    #[test]
+    fn equal_scores_at_turn_limit_draw() {
+        let mut g = Game::with_victory(2, (10, 10), Victory::TurnLimit(1));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (5, 5), unit::Category::Knight).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.game_over().result, Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn player_sees_only_units_in_vision_range() {
+        let mut g = Game::new(2, (30, 30));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (5, 5), unit::Category::Knight).unwrap();
+        g.add_unit(1, (20, 20), unit::Category::Knight).unwrap();
+        let visible: Vec<usize> = g.visible_units(0).iter().map(|unit| unit.id).collect();
+        assert_eq!(visible, vec![0, 1]);
+        let visible: Vec<usize> = g.visible_units(1).iter().map(|unit| unit.id).collect();
+        assert_eq!(visible, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn visible_tiles_stay_on_the_board() {
+        let mut g = Game::new(2, (30, 30));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        let tiles = g.visible_tiles(0);
+        // Fields with x + y <= 10.
+        assert_eq!(tiles.len(), 66);
+        assert!(tiles.contains(&(10, 0)));
+        assert!(tiles.contains(&(5, 5)));
+        assert!(!tiles.contains(&(6, 5)));
+        assert!(g.visible_tiles(1).is_empty());
+    }
+
+    #[test]
+    fn moving_unit_halts_on_enemy_sighting() {
+        let mut g = Game::new(2, (30, 30));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (0, 13), unit::Category::Knight).unwrap();
+        g.move_unit(0, (0, 8)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (0, 3));
+        assert_match!(g.get_unit(0).unwrap().state, unit::State::Idle);
+    }
+
+    #[test]
+    fn moving_unit_ignores_enemies_seen_before() {
+        let mut g = Game::new(2, (30, 30));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (0, 10), unit::Category::Knight).unwrap();
+        g.move_unit(0, (0, 5)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (0, 5));
+    }
+
+    #[test]
+    fn attacking_unit_does_not_halt_on_enemy_sighting() {
+        let mut g = Game::new(2, (30, 30));
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (0, 13), unit::Category::Knight).unwrap();
+        g.attack_position(0, (0, 8)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (0, 8));
+    }
+
+    #[test]
+    fn position_outside_one_of_the_sides_is_rejected() {
+        let mut g = Game::new(2, (10, 10));
+        assert_match!(
+            g.add_unit(0, (5, 20), unit::Category::Knight),
+            Err(GameError::PositionOutsideTheBoard(5, 20))
+        );
+    }
+
+    #[test]
+    fn units_do_not_enter_impassable_terrain() {
+        let map = terrain::Map::parse("..~\n..#\n...\n").unwrap();
+        let mut g = Game::with_map(2, map, Victory::default());
+        assert_match!(
+            g.add_unit(0, (2, 0), unit::Category::Knight),
+            Err(GameError::ImpassableTerrain(2, 0))
+        );
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        assert_match!(
+            g.move_unit(0, (2, 1)),
+            Err(GameError::ImpassableTerrain(2, 1))
+        );
+    }
+
+    #[test]
+    fn units_go_around_water() {
+        let map = terrain::Map::parse("....\n.~~.\n....\n").unwrap();
+        let mut g = Game::with_map(2, map, Victory::default());
+        g.add_unit(0, (0, 1), unit::Category::Knight).unwrap();
+        g.move_unit(0, (3, 1)).unwrap();
+        g.resolve_moves();
+        assert_eq!(g.get_unit(0).unwrap().position, (3, 1));
+    }
+
+    #[test]
+    fn forest_shortens_the_reach() {
+        let map = terrain::Map::parse(&"FFFFFFFFFFFF\n".repeat(12)).unwrap();
+        let mut g = Game::with_map(2, map, Victory::default());
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.move_unit(0, (5, 0)).unwrap();
+        assert_match!(
+            g.move_unit(0, (6, 0)),
+            Err(GameError::MoveOutsideUnitsReach(6, 0))
+        );
+    }
+
+    #[test]
+    fn defense_bonus_turns_lost_battle_into_draw() {
+        let map = terrain::Map::parse("...F\n....\n").unwrap();
+        let mut g = Game::with_map(2, map, Victory::default());
+        g.add_unit(0, (2, 0), unit::Category::Cavalry).unwrap();
+        g.add_unit(1, (3, 0), unit::Category::Knight).unwrap();
+        g.attack_position(0, (3, 0)).unwrap();
+        let battles = g.resolve_moves();
+        assert_eq!(battles[0].outcome, battle::Outcome::Draw);
+        assert_eq!(g.units().len(), 2);
+    }
+
+    #[test]
+    fn defense_bonus_turns_draw_into_defender_win() {
+        let map = terrain::Map::parse("...^\n....\n").unwrap();
+        let mut g = Game::with_map(2, map, Victory::default());
+        g.add_unit(0, (2, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (3, 0), unit::Category::Knight).unwrap();
+        g.attack_position(0, (3, 0)).unwrap();
+        let battles = g.resolve_moves();
+        assert_eq!(battles[0].outcome, battle::Outcome::DefenderWon);
+        assert!(g.get_unit(0).is_err());
+    }
+
+    #[test]
+    fn hills_extend_the_view_and_forest_limits_it() {
+        let mut map = terrain::Map::plains((40, 1));
+        map.set((0, 0), terrain::Terrain::Hills);
+        map.set((39, 0), terrain::Terrain::Forest);
+        let mut g = Game::with_map(2, map, Victory::default());
+        g.add_unit(0, (0, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (13, 0), unit::Category::Knight).unwrap();
+        g.add_unit(1, (39, 0), unit::Category::Knight).unwrap();
+        assert!(g.is_visible(0, (13, 0)));
+        assert!(!g.is_visible(0, (14, 0)));
+        assert!(g.is_visible(1, (32, 0)));
+        assert!(!g.is_visible(1, (31, 0)));
     }
 }
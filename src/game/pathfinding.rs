@@ -0,0 +1,128 @@
+/// Finds routes of the units around the fields they can't enter.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::helpers::{self, Coords};
+
+/// Finds the cheapest route between the fields with A*.
+/// Units step on any of the 8 neighbouring fields, entering the field
+/// costs as much as step_cost says, at least 1.
+/// Fields step_cost returns None for are avoided.
+/// Among the cheapest routes the one going straight
+/// at the destination is preferred.
+///
+/// Returns fields of the route without the starting one.
+/// None if the destination can't be reached for at most max_cost.
+pub fn find_path<F>(
+    from: Coords,
+    to: Coords,
+    board_size: Coords,
+    max_cost: usize,
+    step_cost: F,
+) -> Option<Vec<Coords>>
+where
+    F: Fn(Coords) -> Option<usize>,
+{
+    if from == to {
+        return Some(Vec::new());
+    }
+    let heuristic = |(x, y): Coords| {
+        let x_diff = (x as i64 - to.0 as i64).unsigned_abs() as usize;
+        let y_diff = (y as i64 - to.1 as i64).unsigned_abs() as usize;
+        x_diff.max(y_diff)
+    };
+    let mut costs: HashMap<Coords, usize> = HashMap::new();
+    let mut parents: HashMap<Coords, Coords> = HashMap::new();
+    // Ties are broken by the distance left and then by the order
+    // fields were found in, so the straight route is explored first.
+    let mut open = BinaryHeap::new();
+    let mut found = 0;
+    costs.insert(from, 0);
+    open.push(Reverse((heuristic(from), heuristic(from), found, from)));
+    while let Some(Reverse((_, _, _, field))) = open.pop() {
+        if field == to {
+            return Some(route(&parents, from, to));
+        }
+        let straight = helpers::get_next_field_in_path(field, to);
+        let mut next = helpers::neighbours(field, board_size);
+        next.sort_by_key(|&neighbour| neighbour != straight);
+        for neighbour in next {
+            let cost = match step_cost(neighbour) {
+                Some(val) => costs[&field] + val.max(1),
+                None => continue,
+            };
+            if cost > max_cost {
+                continue;
+            }
+            if costs.get(&neighbour).is_some_and(|&known| known <= cost) {
+                continue;
+            }
+            costs.insert(neighbour, cost);
+            parents.insert(neighbour, field);
+            found += 1;
+            let left = heuristic(neighbour);
+            open.push(Reverse((cost + left, left, found, neighbour)));
+        }
+    }
+    None
+}
+
+/// Follows the found fields back from the destination.
+fn route(parents: &HashMap<Coords, Coords>, from: Coords, to: Coords) -> Vec<Coords> {
+    let mut fields = vec![to];
+    let mut current = to;
+    while let Some(&parent) = parents.get(&current) {
+        if parent == from {
+            break;
+        }
+        fields.push(parent);
+        current = parent;
+    }
+    fields.reverse();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn open_board_route_goes_straight() {
+        let path = find_path((0, 0), (3, 5), (10, 10), 10, |_| Some(1)).unwrap();
+        assert_eq!(path, vec![(1, 1), (2, 2), (3, 3), (3, 4), (3, 5)]);
+    }
+
+    #[test]
+    fn route_avoids_impassable_fields() {
+        let wall = |(x, y): Coords| if x == 2 && y < 4 { None } else { Some(1) };
+        let path = find_path((0, 0), (4, 0), (10, 10), 10, wall).unwrap();
+        assert!(path.iter().all(|&field| wall(field).is_some()));
+        assert_eq!(path.len(), 8);
+        assert_eq!(path.last(), Some(&(4, 0)));
+    }
+
+    #[test]
+    fn unreachable_destination_has_no_route() {
+        let wall = |(x, _): Coords| if x == 2 { None } else { Some(1) };
+        assert_eq!(find_path((0, 0), (4, 0), (10, 10), 20, wall), None);
+    }
+
+    #[test]
+    fn route_longer_than_the_limit_is_rejected() {
+        assert_eq!(find_path((0, 0), (5, 0), (10, 10), 4, |_| Some(1)), None);
+        assert!(find_path((0, 0), (5, 0), (10, 10), 5, |_| Some(1)).is_some());
+    }
+
+    #[test]
+    fn route_goes_around_costly_fields() {
+        let forest = |(x, y): Coords| if x == 1 && y < 2 { Some(4) } else { Some(1) };
+        let path = find_path((0, 0), (2, 0), (10, 10), 10, forest).unwrap();
+        assert_eq!(path, vec![(0, 1), (1, 2), (2, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn route_to_the_starting_field_is_empty() {
+        assert_eq!(find_path((1, 1), (1, 1), (10, 10), 0, |_| Some(1)), Some(vec![]));
+    }
+}
@@ -0,0 +1,487 @@
+/// Defines scenarios, hand made setups of the game read from TOML files.
+///
+/// Scenario describes the board along with its terrain, fields
+/// the players can deploy their units in, the units every player
+/// starts with and the condition the game is won by:
+///
+/// ```toml
+/// players = 2
+///
+/// [board]
+/// width = 4
+/// height = 3
+/// # Optional, see terrain::Map::parse. Board is all plains without it.
+/// terrain = """
+/// ..F.
+/// .~~.
+/// .^..
+/// """
+///
+/// # Optional, game lasts until only one player is left without it.
+/// # Kind is one of "last_standing", "objectives", "turn_limit".
+/// [victory]
+/// kind = "objectives"
+/// objectives = [[3, 1]]
+/// turns = 2
+///
+/// # Optional, units of the player with no zone can stand anywhere.
+/// # Both corners belong to the zone.
+/// [[zones]]
+/// player = 0
+/// from = [0, 0]
+/// to = [3, 0]
+///
+/// # Category is one of "cavalry", "knight", "pickerman".
+/// [[units]]
+/// owner = 0
+/// category = "knight"
+/// position = [0, 0]
+/// ```
+use std::error::Error;
+
+use serde_derive::Deserialize;
+
+use super::error::ScenarioError;
+use super::helpers::Coords;
+use super::terrain::Map;
+use super::unit::Category;
+use super::victory::Victory;
+use super::Game;
+use crate::helpers::file;
+
+/// Scenario as written in the file, before it is validated.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    players: u8,
+    board: BoardEntry,
+    victory: Option<VictoryEntry>,
+    #[serde(default)]
+    zones: Vec<ZoneEntry>,
+    #[serde(default)]
+    units: Vec<UnitEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoardEntry {
+    width: usize,
+    height: usize,
+    terrain: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VictoryEntry {
+    kind: String,
+    turns: Option<u32>,
+    #[serde(default)]
+    objectives: Vec<Coords>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ZoneEntry {
+    player: u8,
+    from: Coords,
+    to: Coords,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UnitEntry {
+    owner: u8,
+    category: String,
+    position: Coords,
+}
+
+/// Fields the player can deploy its units in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zone {
+    pub owner_id: u8,
+    /// Top left corner of the zone.
+    pub from: (usize, usize),
+    /// Bottom right corner of the zone.
+    pub to: (usize, usize),
+}
+
+impl Zone {
+    /// Checks if the field lies inside the zone.
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        (self.from.0..=self.to.0).contains(&x) && (self.from.1..=self.to.1).contains(&y)
+    }
+}
+
+/// Unit the player starts the game with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deployment {
+    pub owner_id: u8,
+    pub category: Category,
+    pub position: (usize, usize),
+}
+
+/// Validated setup of the game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub num_of_players: u8,
+    /// Board along with its terrain.
+    pub map: Map,
+    pub victory: Victory,
+    pub zones: Vec<Zone>,
+    /// Units in the order they are added to the game,
+    /// which gives them their ids.
+    pub units: Vec<Deployment>,
+}
+
+impl Scenario {
+    /// Reads and validates the scenario.
+    /// Errors point to the entry that is not allowed.
+    pub fn parse(text: &str) -> Result<Scenario, ScenarioError> {
+        let raw: ScenarioFile = toml::from_str(text)?;
+        if raw.players < 2 {
+            return Err(ScenarioError::invalid(
+                "players",
+                "there have to be at least 2 players",
+            ));
+        }
+        let map = read_map(&raw.board)?;
+        let victory = match raw.victory.as_ref() {
+            Some(entry) => read_victory(entry, &map)?,
+            None => Victory::LastStanding,
+        };
+        let zones = read_zones(&raw.zones, raw.players, &map)?;
+        let units = read_units(&raw.units, raw.players, &map, &zones)?;
+        Ok(Scenario {
+            num_of_players: raw.players,
+            map,
+            victory,
+            zones,
+            units,
+        })
+    }
+
+    /// Reads the scenario from the file, see parse.
+    pub fn from_file(file_name: &str) -> Result<Scenario, Box<dyn Error>> {
+        let content = file::read(file_name)?;
+        Ok(Scenario::parse(&content)?)
+    }
+
+    /// Returns width and height of the board.
+    pub fn board_size(&self) -> (usize, usize) {
+        self.map.size()
+    }
+
+    /// Creates the game with all of the units deployed.
+    pub fn build(&self) -> Game {
+        let mut game = Game::with_map(self.num_of_players, self.map.clone(), self.victory.clone());
+        for unit in self.units.iter() {
+            game.add_unit(unit.owner_id, unit.position, unit.category)
+                .expect("validated scenario places units on free fields");
+        }
+        game
+    }
+}
+
+fn read_map(board: &BoardEntry) -> Result<Map, ScenarioError> {
+    let size = (board.width, board.height);
+    if size.0 == 0 || size.1 == 0 {
+        return Err(ScenarioError::invalid("board", "board can't be empty"));
+    }
+    let terrain = match board.terrain.as_ref() {
+        Some(val) => val,
+        None => return Ok(Map::plains(size)),
+    };
+    let map =
+        Map::parse(terrain).map_err(|err| ScenarioError::invalid("board.terrain", err.to_string()))?;
+    if map.size() != size {
+        return Err(ScenarioError::invalid(
+            "board.terrain",
+            format!(
+                "terrain is {}x{} but the board is {}x{}",
+                map.size().0,
+                map.size().1,
+                size.0,
+                size.1
+            ),
+        ));
+    }
+    Ok(map)
+}
+
+fn read_victory(entry: &VictoryEntry, map: &Map) -> Result<Victory, ScenarioError> {
+    let turns = || match entry.turns {
+        Some(0) => Err(ScenarioError::invalid(
+            "victory.turns",
+            "number of turns has to be positive",
+        )),
+        Some(val) => Ok(val),
+        None => Err(ScenarioError::invalid(
+            "victory.turns",
+            format!("is required by \"{}\"", entry.kind),
+        )),
+    };
+    match entry.kind.as_str() {
+        "last_standing" => Ok(Victory::LastStanding),
+        "turn_limit" => Ok(Victory::TurnLimit(turns()?)),
+        "objectives" => {
+            if entry.objectives.is_empty() {
+                return Err(ScenarioError::invalid(
+                    "victory.objectives",
+                    "there has to be at least one objective",
+                ));
+            }
+            for (i, &field) in entry.objectives.iter().enumerate() {
+                check_field(&format!("victory.objectives[{}]", i), field, map)?;
+            }
+            Ok(Victory::Objectives {
+                fields: entry.objectives.clone(),
+                turns: turns()?,
+            })
+        }
+        kind => Err(ScenarioError::invalid(
+            "victory.kind",
+            format!("unknown victory condition \"{}\"", kind),
+        )),
+    }
+}
+
+fn read_zones(entries: &[ZoneEntry], players: u8, map: &Map) -> Result<Vec<Zone>, ScenarioError> {
+    let (width, height) = map.size();
+    let mut zones = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        check_owner(&format!("zones[{}].player", i), entry.player, players)?;
+        let (from, to) = (entry.from, entry.to);
+        if to.0 >= width || to.1 >= height {
+            return Err(ScenarioError::invalid(
+                format!("zones[{}].to", i),
+                "zone has to lie on the board",
+            ));
+        }
+        if from.0 > to.0 || from.1 > to.1 {
+            return Err(ScenarioError::invalid(
+                format!("zones[{}].from", i),
+                "corner has to lie above and left of \"to\"",
+            ));
+        }
+        zones.push(Zone {
+            owner_id: entry.player,
+            from,
+            to,
+        });
+    }
+    Ok(zones)
+}
+
+fn read_units(
+    entries: &[UnitEntry],
+    players: u8,
+    map: &Map,
+    zones: &[Zone],
+) -> Result<Vec<Deployment>, ScenarioError> {
+    let mut units: Vec<Deployment> = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        check_owner(&format!("units[{}].owner", i), entry.owner, players)?;
+        let category = match entry.category.as_str() {
+            "cavalry" => Category::Cavalry,
+            "knight" => Category::Knight,
+            "pickerman" => Category::Pickerman,
+            other => {
+                return Err(ScenarioError::invalid(
+                    format!("units[{}].category", i),
+                    format!("unknown unit category \"{}\"", other),
+                ))
+            }
+        };
+        let entry_name = format!("units[{}].position", i);
+        check_field(&entry_name, entry.position, map)?;
+        if let Some(other) = units.iter().position(|u| u.position == entry.position) {
+            return Err(ScenarioError::invalid(
+                entry_name,
+                format!("field is already taken by units[{}]", other),
+            ));
+        }
+        let mut own_zones = zones.iter().filter(|z| z.owner_id == entry.owner).peekable();
+        if own_zones.peek().is_some() && !own_zones.any(|z| z.contains(entry.position)) {
+            return Err(ScenarioError::invalid(
+                entry_name,
+                format!("unit lies outside the zones of player {}", entry.owner),
+            ));
+        }
+        units.push(Deployment {
+            owner_id: entry.owner,
+            category,
+            position: entry.position,
+        });
+    }
+    if let Some(player) = (0..players).find(|&p| units.iter().all(|u| u.owner_id != p)) {
+        return Err(ScenarioError::invalid(
+            "units",
+            format!("player {} has no units", player),
+        ));
+    }
+    Ok(units)
+}
+
+fn check_owner(entry: &str, owner: u8, players: u8) -> Result<(), ScenarioError> {
+    if owner >= players {
+        return Err(ScenarioError::invalid(
+            entry,
+            format!("there are only {} players", players),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks if units can stand on the field.
+fn check_field(entry: &str, (x, y): Coords, map: &Map) -> Result<(), ScenarioError> {
+    let (width, height) = map.size();
+    if x >= width || y >= height {
+        return Err(ScenarioError::invalid(entry, "field lies outside the board"));
+    }
+    if map.get((x, y)).movement_cost().is_none() {
+        return Err(ScenarioError::invalid(entry, "units can't enter the field"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::terrain::Terrain;
+
+    const SKIRMISH: &str = r#"
+players = 2
+
+[board]
+width = 4
+height = 3
+terrain = """
+..F.
+.~~.
+.^..
+"""
+
+[victory]
+kind = "objectives"
+objectives = [[3, 1]]
+turns = 2
+
+[[zones]]
+player = 0
+from = [0, 0]
+to = [3, 0]
+
+[[units]]
+owner = 0
+category = "knight"
+position = [0, 0]
+
+[[units]]
+owner = 1
+category = "pickerman"
+position = [1, 2]
+"#;
+
+    /// Returns the entry the scenario is rejected for.
+    fn rejected_entry(text: &str) -> String {
+        match Scenario::parse(text) {
+            Err(ScenarioError::InvalidEntry { entry, .. }) => entry,
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("scenario should be rejected"),
+        }
+    }
+
+    #[test]
+    fn scenario_is_parsed() {
+        let scenario = Scenario::parse(SKIRMISH).unwrap();
+        assert_eq!(scenario.num_of_players, 2);
+        assert_eq!(scenario.board_size(), (4, 3));
+        assert_eq!(scenario.map.get((2, 0)), Terrain::Forest);
+        assert_eq!(
+            scenario.victory,
+            Victory::Objectives {
+                fields: vec![(3, 1)],
+                turns: 2
+            }
+        );
+        assert!(scenario.zones[0].contains((3, 0)));
+        assert!(!scenario.zones[0].contains((0, 1)));
+        assert_eq!(
+            scenario.units[1],
+            Deployment {
+                owner_id: 1,
+                category: Category::Pickerman,
+                position: (1, 2),
+            }
+        );
+    }
+
+    #[test]
+    fn optional_entries_have_defaults() {
+        let text = r#"
+players = 2
+board = { width = 3, height = 2 }
+units = [
+    { owner = 0, category = "cavalry", position = [0, 0] },
+    { owner = 1, category = "cavalry", position = [2, 1] },
+]
+"#;
+        let scenario = Scenario::parse(text).unwrap();
+        assert_eq!(scenario.map, Map::plains((3, 2)));
+        assert_eq!(scenario.victory, Victory::LastStanding);
+        assert!(scenario.zones.is_empty());
+    }
+
+    #[test]
+    fn built_game_has_all_units_deployed() {
+        let game = Scenario::parse(SKIRMISH).unwrap().build();
+        assert_eq!(game.board_size, (4, 3));
+        assert_eq!(game.map().get((1, 1)), Terrain::Water);
+        assert_eq!(game.units.len(), 2);
+        assert_eq!(game.units[0].position, (0, 0));
+        assert_eq!(game.units[1].owner_id, 1);
+    }
+
+    #[test]
+    fn malformed_scenario_is_a_syntax_error() {
+        assert!(matches!(
+            Scenario::parse("players = \"two\""),
+            Err(ScenarioError::Syntax(_))
+        ));
+        let typo = SKIRMISH.replace("owner = 1", "ownr = 1");
+        assert!(matches!(
+            Scenario::parse(&typo),
+            Err(ScenarioError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_entries_are_pointed_to() {
+        let cases = [
+            ("players = 2", "players = 1", "players"),
+            ("width = 4", "width = 5", "board.terrain"),
+            (".^..", ".x..", "board.terrain"),
+            ("\"objectives\"", "\"capture\"", "victory.kind"),
+            ("[[3, 1]]", "[[1, 1]]", "victory.objectives[0]"),
+            ("turns = 2", "turns = 0", "victory.turns"),
+            ("player = 0", "player = 2", "zones[0].player"),
+            ("to = [3, 0]", "to = [4, 0]", "zones[0].to"),
+            ("\"pickerman\"", "\"archer\"", "units[1].category"),
+            ("position = [1, 2]", "position = [0, 0]", "units[1].position"),
+            ("position = [1, 2]", "position = [1, 1]", "units[1].position"),
+            ("position = [0, 0]", "position = [0, 2]", "units[0].position"),
+            ("players = 2", "players = 3", "units"),
+        ];
+        for &(from, to, entry) in cases.iter() {
+            assert_eq!(rejected_entry(&SKIRMISH.replace(from, to)), entry, "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn scenario_is_read_from_file() {
+        let tmp = file::create_temp_with_content(SKIRMISH).unwrap();
+        let scenario = Scenario::from_file(tmp.path().to_str().unwrap()).unwrap();
+        assert_eq!(scenario, Scenario::parse(SKIRMISH).unwrap());
+    }
+}
@@ -0,0 +1,200 @@
+/// Defines terrain of the board's fields and the maps made of them.
+use std::error::Error;
+use std::fmt;
+
+use super::error::MapError;
+use super::helpers::Coords;
+use crate::helpers::file;
+
+/// Kind of the board's field.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Terrain {
+    /// Open field, nothing to slow down or cover the units.
+    Plains,
+    /// Slows units down, gives cover and limits the view.
+    Forest,
+    /// Slows units down, gives cover and extends the view.
+    Hills,
+    /// Units can't enter it.
+    Water,
+    /// Units can't enter it.
+    Wall,
+}
+
+impl Terrain {
+    /// Number of steps it takes to enter the field.
+    /// None if units can't enter it at all.
+    pub fn movement_cost(self) -> Option<usize> {
+        match self {
+            Terrain::Plains => Some(1),
+            Terrain::Forest | Terrain::Hills => Some(2),
+            Terrain::Water | Terrain::Wall => None,
+        }
+    }
+
+    /// Change of the vision range of the units standing on the field.
+    pub fn vision_modifier(self) -> isize {
+        match self {
+            Terrain::Hills => 3,
+            Terrain::Forest => -3,
+            _ => 0,
+        }
+    }
+
+    /// Bonus of the unit defending the field in the battle.
+    /// Each point turns the battle's outcome one step in its favour,
+    /// see Game::battle.
+    pub fn defense_bonus(self) -> isize {
+        match self {
+            Terrain::Forest | Terrain::Hills => 1,
+            _ => 0,
+        }
+    }
+
+    /// Returns terrain of the map file's character.
+    pub fn from_char(tile: char) -> Option<Terrain> {
+        match tile {
+            '.' => Some(Terrain::Plains),
+            'F' => Some(Terrain::Forest),
+            '^' => Some(Terrain::Hills),
+            '~' => Some(Terrain::Water),
+            '#' => Some(Terrain::Wall),
+            _ => None,
+        }
+    }
+
+    /// Returns character of the terrain in the map file.
+    pub fn to_char(self) -> char {
+        match self {
+            Terrain::Plains => '.',
+            Terrain::Forest => 'F',
+            Terrain::Hills => '^',
+            Terrain::Water => '~',
+            Terrain::Wall => '#',
+        }
+    }
+}
+
+/// Terrain of every field of the board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Map {
+    size: Coords,
+    /// Fields stored row by row.
+    tiles: Vec<Terrain>,
+}
+
+impl Map {
+    /// Creates map of the given size covered with plains.
+    pub fn plains(size: Coords) -> Map {
+        Map {
+            size,
+            tiles: vec![Terrain::Plains; size.0 * size.1],
+        }
+    }
+
+    /// Reads the map from the text, one line per row of the board.
+    /// Each character is the single field:
+    ///     '.' plains, 'F' forest, '^' hills, '~' water, '#' wall.
+    /// All of the rows have to be equally long.
+    pub fn parse(text: &str) -> Result<Map, MapError> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let width = match rows.first() {
+            Some(row) => row.chars().count(),
+            None => return Err(MapError::Empty),
+        };
+        let mut tiles = Vec::with_capacity(width * rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(MapError::RaggedRow(y));
+            }
+            for (x, tile) in row.chars().enumerate() {
+                let terrain = Terrain::from_char(tile).ok_or(MapError::UnknownTile(tile, x, y))?;
+                tiles.push(terrain);
+            }
+        }
+        Ok(Map {
+            size: (width, rows.len()),
+            tiles,
+        })
+    }
+
+    /// Reads the map from the file, see parse.
+    pub fn from_file(file_name: &str) -> Result<Map, Box<dyn Error>> {
+        let content = file::read(file_name)?;
+        Ok(Map::parse(&content)?)
+    }
+
+    /// Returns width and height of the map.
+    pub fn size(&self) -> Coords {
+        self.size
+    }
+
+    /// Returns terrain of the field.
+    /// Panics if the field lies outside the map.
+    pub fn get(&self, (x, y): Coords) -> Terrain {
+        assert!(x < self.size.0 && y < self.size.1);
+        self.tiles[y * self.size.0 + x]
+    }
+
+    /// Changes terrain of the field.
+    /// Panics if the field lies outside the map.
+    pub fn set(&mut self, (x, y): Coords, terrain: Terrain) {
+        assert!(x < self.size.0 && y < self.size.1);
+        self.tiles[y * self.size.0 + x] = terrain;
+    }
+}
+
+impl fmt::Display for Map {
+    /// Writes the map the way parse reads it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.tiles.chunks(self.size.0) {
+            let line: String = row.iter().map(|tile| tile.to_char()).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn map_is_parsed_row_by_row() {
+        let map = Map::parse(".F^\n~#.\n").unwrap();
+        assert_eq!(map.size(), (3, 2));
+        assert_eq!(map.get((1, 0)), Terrain::Forest);
+        assert_eq!(map.get((2, 0)), Terrain::Hills);
+        assert_eq!(map.get((0, 1)), Terrain::Water);
+        assert_eq!(map.get((1, 1)), Terrain::Wall);
+    }
+
+    #[test]
+    fn written_map_is_parsed_back() {
+        let text = ".F^\n~#.\n";
+        assert_eq!(Map::parse(text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn invalid_maps_are_rejected() {
+        assert!(matches!(Map::parse("\n\n"), Err(MapError::Empty)));
+        assert!(matches!(Map::parse("...\n..\n"), Err(MapError::RaggedRow(1))));
+        assert!(matches!(
+            Map::parse("..\n.x\n"),
+            Err(MapError::UnknownTile('x', 1, 1))
+        ));
+    }
+
+    #[test]
+    fn map_is_read_from_file() {
+        let content = "..\n^^\n";
+        let tmp = file::create_temp_with_content(content).unwrap();
+        let map = Map::from_file(tmp.path().to_str().unwrap()).unwrap();
+        assert_eq!(map, Map::parse(content).unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 /// Defines Unit datatype and any related structs.
 use std::cmp::Ordering;
+use std::collections::HashSet;
 
 /// Unit represents single soldier entity
 /// inside the game.
@@ -24,14 +25,20 @@ pub struct Unit {
 #[derive(Debug)]
 pub struct MovingWrapper {
     pub moves_made: usize,
+    /// Movement cost of the fields the Unit entered so far.
+    pub cost_spent: usize,
     pub unit_id: usize,
+    /// Ids of the enemy units the Unit saw before it started moving.
+    pub sighted: HashSet<usize>,
 }
 
 impl MovingWrapper {
     pub fn new(unit_id: usize) -> MovingWrapper {
         MovingWrapper {
             moves_made: 0,
+            cost_spent: 0,
             unit_id,
+            sighted: HashSet::new(),
         }
     }
 }
@@ -60,7 +67,7 @@ impl Eq for MovingWrapper {}
 /// As in Rock-Scissor-Paper each category
 /// has one other as it's weakness and
 /// another one as it's advantage.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Category {
     /// Beats Knight, loses to Pickerman.
     Cavalry,
@@ -70,6 +77,19 @@ pub enum Category {
     Pickerman,
 }
 
+impl Category {
+    /// Checks if the unit of this category wins the battle
+    /// with the unit of the other one.
+    pub fn beats(self, other: Category) -> bool {
+        matches!(
+            (self, other),
+            (Category::Cavalry, Category::Knight)
+                | (Category::Knight, Category::Pickerman)
+                | (Category::Pickerman, Category::Cavalry)
+        )
+    }
+}
+
 /// Unit statistics determinig it's ability to move.
 #[derive(Copy, Clone, Debug)]
 pub struct Stats {
@@ -0,0 +1,37 @@
+/// Defines conditions ending the game and their outcome.
+use super::helpers::Coords;
+
+/// Condition the game is won by, chosen when the game is created.
+/// Last player with units standing always wins,
+/// whatever condition was chosen.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Victory {
+    /// Game lasts until only one player has units left.
+    #[default]
+    LastStanding,
+    /// Player whose units stand on all of the fields
+    /// at the end of the given number of turns in a row wins.
+    Objectives { fields: Vec<Coords>, turns: u32 },
+    /// After the given number of turns the player with the highest score wins.
+    /// Score is the number of the player's units standing.
+    TurnLimit(u32),
+}
+
+/// How the finished game ended.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    /// Player with the given id won.
+    Won(u8),
+    /// Nobody won. Either the last players were eliminated at once
+    /// or their scores were equal once the turn limit was reached.
+    Draw,
+}
+
+/// State of the game after the turn was resolved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Verdict {
+    /// Players who lost their last unit in the resolved turn.
+    pub eliminated: Vec<u8>,
+    /// Set if the game is over.
+    pub result: Option<GameResult>,
+}
@@ -7,7 +7,7 @@ use std::io::Read;
 use std::io::Write;
 
 /// Reads the whole file under path to the string.
-pub fn read(file_name: &str) -> Result<String, Box<Error>> {
+pub fn read(file_name: &str) -> Result<String, Box<dyn Error>> {
     let mut file = File::open(file_name)?;
     let mut content = String::new();
     file.read_to_string(&mut content)?;
@@ -17,7 +17,7 @@ pub fn read(file_name: &str) -> Result<String, Box<Error>> {
 
 /// Creates temp file with the given content
 #[cfg(test)]
-pub fn create_temp_with_content(content: &str) -> Result<tempfile::NamedTempFile, Box<Error>> {
+pub fn create_temp_with_content(content: &str) -> Result<tempfile::NamedTempFile, Box<dyn Error>> {
     let mut tmp_file = tempfile::NamedTempFile::new()?;
     tmp_file.write_all(content.as_bytes())?;
     Ok(tmp_file)
@@ -35,7 +35,7 @@ mod tests {
     #[test]
     fn read_content_is_proper() {
         let content = "Some file content";
-        let file = create_temp_with_content(&content).unwrap();
+        let file = create_temp_with_content(content).unwrap();
         let file_content = read(file.path().to_str().unwrap()).unwrap();
         assert_eq!(content, file_content);
     }
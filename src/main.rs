@@ -5,5 +5,7 @@ use glib::comm::Server;
 
 fn main() {
     let mut serv = Server::new(String::from_str("config.toml").unwrap());
+    let shutdown = serv.shutdown_handle();
+    ctrlc::set_handler(move || shutdown.trigger()).unwrap();
     serv.run();
 }
@@ -0,0 +1,195 @@
+#![cfg(feature = "async-server")]
+
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use glib::comm::{AsyncServer, ShutdownHandle};
+use glib::config::ServerConfig;
+
+use common::{handshake, hello_payload, receive, send, send_as, SKEY};
+
+/// Starts async server on a random port in the background.
+fn start_server(grace_ms: u64) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<()>) {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = grace_ms;
+    config.max_message_len = 64;
+    start_server_with(config)
+}
+
+fn start_server_with(config: ServerConfig) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<()>) {
+    let mut server = AsyncServer::from_config(config);
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let thread = thread::spawn(move || server.run());
+    (addr, shutdown, thread)
+}
+
+#[test]
+fn many_clients_are_served_by_single_thread() {
+    let (addr, shutdown, thread) = start_server(500);
+    let mut clients: Vec<TcpStream> = (0..50).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    for client in clients.iter_mut() {
+        send(client, 0, &hello_payload());
+    }
+    for client in clients.iter_mut() {
+        assert_eq!(receive(client).0, 1);
+    }
+
+    shutdown.trigger();
+    for client in clients.iter_mut() {
+        assert_eq!(receive(client).0, 2);
+    }
+    thread.join().unwrap();
+}
+
+#[test]
+fn unknown_request_keeps_connection_open() {
+    let (addr, shutdown, thread) = start_server(500);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 4321, &[]);
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &404u32.to_le_bytes());
+
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn oversized_message_is_rejected_before_it_is_read() {
+    let (addr, shutdown, thread) = start_server(500);
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut header = SKEY.to_vec();
+    header.extend(&0u32.to_le_bytes());
+    header.extend(&u32::MAX.to_le_bytes());
+    client.write_all(&header).unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &405u32.to_le_bytes());
+
+    let mut buf = [0; 16];
+    assert_eq!(client.read(&mut buf).unwrap_or(0), 0);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn pipelined_requests_are_all_answered() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut raw = Vec::new();
+    for id in &[0u32, 4321, 1] {
+        let payload = if *id == 0 { hello_payload() } else { Vec::new() };
+        raw.extend_from_slice(SKEY);
+        raw.extend(&id.to_le_bytes());
+        raw.extend(&(payload.len() as u32).to_le_bytes());
+        raw.extend(payload);
+    }
+    client.write_all(&raw).unwrap();
+    assert_eq!(receive(&mut client).0, 1);
+    assert_eq!(receive(&mut client).0, 0);
+    assert_eq!(receive(&mut client).0, 3);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn invalid_server_key_is_rejected_without_full_header() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(b"XX").unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &401u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn ping_keeps_connection_alive() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.idle_timeout_ms = 300;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut client = TcpStream::connect(addr).unwrap();
+    for _ in 0..4 {
+        thread::sleep(Duration::from_millis(150));
+        send(&mut client, 1, &[]);
+        assert_eq!(receive(&mut client).0, 3);
+    }
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn idle_client_is_disconnected() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.idle_timeout_ms = 200;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut client = TcpStream::connect(addr).unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &408u32.to_le_bytes());
+
+    let mut buf = [0; 16];
+    assert_eq!(client.read(&mut buf).unwrap_or(0), 0);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn unfinished_message_times_out() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.read_timeout_ms = 200;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(SKEY).unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &408u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn lobby_players_are_told_who_left() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut alice = TcpStream::connect(addr).unwrap();
+    let alice_token = handshake(&mut alice, "alice");
+    let mut bob = TcpStream::connect(addr).unwrap();
+    let bob_token = handshake(&mut bob, "bob");
+
+    let mut create = vec![2];
+    create.extend(&10u32.to_le_bytes());
+    create.extend(&10u32.to_le_bytes());
+    send_as(&mut alice, &alice_token, 4, &create);
+    let (id, payload) = receive(&mut alice);
+    assert_eq!(id, 7);
+    send_as(&mut bob, &bob_token, 6, &payload[..4]);
+    assert_eq!(receive(&mut bob).0, 7);
+
+    send_as(&mut bob, &bob_token, 7, &[]);
+    assert_eq!(receive(&mut bob), (8, vec![]));
+    // Bob authenticated second, so his player id is 1.
+    assert_eq!(receive(&mut alice), (15, 1u32.to_le_bytes().to_vec()));
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
@@ -0,0 +1,58 @@
+//! Minimal client used by the integration tests.
+#![allow(dead_code)]
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+pub const SKEY: &[u8] = b"RG";
+
+/// Payload of the Hello speaking the server's protocol version.
+pub fn hello_payload() -> Vec<u8> {
+    let mut payload = glib::comm::PROTOCOL_VERSION.to_le_bytes().to_vec();
+    payload.extend_from_slice(b"tester");
+    payload
+}
+
+/// Sends single message to the server.
+pub fn send(stream: &mut TcpStream, id: u32, payload: &[u8]) {
+    let mut raw = SKEY.to_vec();
+    raw.extend(&id.to_le_bytes());
+    raw.extend(&(payload.len() as u32).to_le_bytes());
+    raw.extend_from_slice(payload);
+    stream.write_all(&raw).unwrap();
+}
+
+/// Reads single response returning its id and payload.
+pub fn receive(stream: &mut TcpStream) -> (u32, Vec<u8>) {
+    let mut header = [0; 10];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(&header[..2], SKEY);
+    let mut id = [0; 4];
+    id.copy_from_slice(&header[2..6]);
+    let mut len = [0; 4];
+    len.copy_from_slice(&header[6..10]);
+    let mut payload = vec![0; u32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut payload).unwrap();
+    (u32::from_le_bytes(id), payload)
+}
+
+/// Greets the server and authenticates as the given player.
+/// Returns the player token.
+pub fn handshake(stream: &mut TcpStream, name: &str) -> Vec<u8> {
+    send(stream, 0, &hello_payload());
+    assert_eq!(receive(stream).0, 1);
+    send(stream, 2, name.as_bytes());
+    let (id, payload) = receive(stream);
+    assert_eq!(id, 4);
+    payload[4..].to_vec()
+}
+
+/// Sends game affecting message prefixed with the player token.
+pub fn send_as(stream: &mut TcpStream, token: &[u8], id: u32, payload: &[u8]) {
+    let mut full = (token.len() as u32).to_le_bytes().to_vec();
+    full.extend_from_slice(token);
+    full.extend_from_slice(payload);
+    send(stream, id, &full);
+}
@@ -0,0 +1,767 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use glib::comm::{Server, ShutdownHandle};
+use glib::config::ServerConfig;
+use glib::game::{mapgen, terrain::Terrain};
+
+use common::{handshake, hello_payload, receive, send, send_as, SKEY};
+
+/// Starts server on a random port in the background.
+fn start_server(grace_ms: u64) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<()>) {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = grace_ms;
+    start_server_with(config)
+}
+
+fn start_server_with(config: ServerConfig) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<()>) {
+    let mut server = Server::from_config(config);
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let thread = thread::spawn(move || server.run());
+    (addr, shutdown, thread)
+}
+
+#[test]
+fn server_stops_without_connections() {
+    let (_, shutdown, thread) = start_server(100);
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn connected_client_is_said_goodbye() {
+    let (addr, shutdown, thread) = start_server(1000);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+
+    shutdown.trigger();
+    assert_eq!(receive(&mut client).0, 2);
+    thread.join().unwrap();
+}
+
+#[test]
+fn unknown_request_keeps_connection_open() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 4321, &[]);
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &404u32.to_le_bytes());
+
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn unfinished_request_is_severed_after_grace_period() {
+    let (addr, shutdown, thread) = start_server(200);
+    let mut client = TcpStream::connect(addr).unwrap();
+    // Only part of the header, server waits for the rest.
+    client.write_all(SKEY).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let start = Instant::now();
+    shutdown.trigger();
+    thread.join().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let mut buf = [0; 16];
+    assert_eq!(client.read(&mut buf).unwrap_or(0), 0);
+}
+
+#[test]
+fn connections_over_the_limit_are_refused() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.worker_threads = 1;
+    config.pending_connections = 1;
+    let (addr, shutdown, thread) = start_server_with(config);
+
+    let mut handled = TcpStream::connect(addr).unwrap();
+    send(&mut handled, 0, &hello_payload());
+    assert_eq!(receive(&mut handled).0, 1);
+
+    let mut queued = TcpStream::connect(addr).unwrap();
+    let mut refused = TcpStream::connect(addr).unwrap();
+    let (id, payload) = receive(&mut refused);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &503u32.to_le_bytes());
+
+    // Queued connection is handled once the worker is free.
+    drop(handled);
+    send(&mut queued, 0, &hello_payload());
+    assert_eq!(receive(&mut queued).0, 1);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn oversized_message_is_rejected_before_it_is_read() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.max_message_len = 64;
+    let (addr, shutdown, thread) = start_server_with(config);
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut header = SKEY.to_vec();
+    header.extend(&0u32.to_le_bytes());
+    header.extend(&u32::MAX.to_le_bytes());
+    client.write_all(&header).unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &405u32.to_le_bytes());
+
+    let mut buf = [0; 16];
+    assert_eq!(client.read(&mut buf).unwrap_or(0), 0);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn pipelined_requests_are_all_answered() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut raw = Vec::new();
+    for id in &[0u32, 4321, 1] {
+        let payload = if *id == 0 { hello_payload() } else { Vec::new() };
+        raw.extend_from_slice(SKEY);
+        raw.extend(&id.to_le_bytes());
+        raw.extend(&(payload.len() as u32).to_le_bytes());
+        raw.extend(payload);
+    }
+    client.write_all(&raw).unwrap();
+    assert_eq!(receive(&mut client).0, 1);
+    assert_eq!(receive(&mut client).0, 0);
+    assert_eq!(receive(&mut client).0, 3);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn invalid_server_key_is_rejected_without_full_header() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(b"XX").unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &401u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn ping_keeps_connection_alive() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.idle_timeout_ms = 300;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut client = TcpStream::connect(addr).unwrap();
+    for _ in 0..4 {
+        thread::sleep(Duration::from_millis(150));
+        send(&mut client, 1, &[]);
+        assert_eq!(receive(&mut client).0, 3);
+    }
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn idle_client_is_disconnected() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.idle_timeout_ms = 200;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut client = TcpStream::connect(addr).unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &408u32.to_le_bytes());
+
+    let mut buf = [0; 16];
+    assert_eq!(client.read(&mut buf).unwrap_or(0), 0);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn unfinished_message_times_out() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.read_timeout_ms = 200;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(SKEY).unwrap();
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &408u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn welcome_negotiates_protocol_version() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 0, &0u32.to_le_bytes());
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &406u32.to_le_bytes());
+
+    send(&mut client, 0, &hello_payload());
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 1);
+    let version = glib::comm::PROTOCOL_VERSION.to_le_bytes();
+    assert_eq!(&payload[..4], &version);
+    assert_eq!(&payload[4..8], &version);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn second_hello_is_rejected_by_state() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+    send(&mut client, 0, &hello_payload());
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &409u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn authenticated_player_gets_token() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+    send(&mut client, 2, b"alice");
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 4);
+    assert!(payload.len() > 4);
+
+    let mut other = TcpStream::connect(addr).unwrap();
+    send(&mut other, 0, &hello_payload());
+    assert_eq!(receive(&mut other).0, 1);
+    send(&mut other, 2, b"alice");
+    let (id, payload) = receive(&mut other);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &411u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn dropped_player_can_reconnect() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+    send(&mut client, 2, b"alice");
+    let (_, payload) = receive(&mut client);
+    let (player, token) = payload.split_at(4);
+    drop(client);
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    send(&mut client, 0, &hello_payload());
+    assert_eq!(receive(&mut client).0, 1);
+    send(&mut client, 3, token);
+    let (id, payload) = receive(&mut client);
+    assert_eq!(id, 5);
+    assert_eq!(&payload[..4], player);
+    assert_eq!(&payload[4..8], &0u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn game_starts_when_lobby_is_ready() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut alice = TcpStream::connect(addr).unwrap();
+    let alice_token = handshake(&mut alice, "alice");
+    let mut bob = TcpStream::connect(addr).unwrap();
+    let bob_token = handshake(&mut bob, "bob");
+
+    let mut create = vec![2];
+    create.extend(&10u32.to_le_bytes());
+    create.extend(&10u32.to_le_bytes());
+    send_as(&mut alice, &alice_token, 4, &create);
+    let (id, payload) = receive(&mut alice);
+    assert_eq!(id, 7);
+    let lobby_id = payload[..4].to_vec();
+
+    send_as(&mut bob, &bob_token, 5, &[]);
+    let (id, payload) = receive(&mut bob);
+    assert_eq!(id, 6);
+    assert_eq!(&payload[..4], &1u32.to_le_bytes());
+    assert_eq!(&payload[4..8], &lobby_id[..]);
+
+    send_as(&mut bob, &bob_token, 6, &lobby_id);
+    let (id, payload) = receive(&mut bob);
+    assert_eq!(id, 7);
+    assert_eq!(payload[4], 1);
+
+    send_as(&mut alice, &alice_token, 8, &[1]);
+    assert_eq!(receive(&mut alice), (9, vec![0]));
+    send_as(&mut bob, &bob_token, 8, &[1]);
+    assert_eq!(receive(&mut bob), (9, vec![1]));
+
+    // Both players are shown the board, all plains with no objectives.
+    let mut setup = 10u32.to_le_bytes().to_vec();
+    setup.extend(&10u32.to_le_bytes());
+    setup.extend(&[0; 100]);
+    setup.extend(&0u32.to_le_bytes());
+    assert_eq!(receive(&mut alice), (19, setup.clone()));
+    assert_eq!(receive(&mut bob), (19, setup));
+
+    // And the units they see.
+    assert_eq!(receive(&mut alice).0, 20);
+    assert_eq!(receive(&mut bob).0, 20);
+
+    // Both players are told the first turn started.
+    let mut turn_started = 0u32.to_le_bytes().to_vec();
+    turn_started.extend(&60000u32.to_le_bytes());
+    assert_eq!(receive(&mut alice), (14, turn_started.clone()));
+    assert_eq!(receive(&mut bob), (14, turn_started));
+
+    // Alice is in game now, lobby requests are not accepted.
+    send_as(&mut alice, &alice_token, 7, &[]);
+    let (id, payload) = receive(&mut alice);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &409u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+/// Starts two player game returning streams and tokens of its players.
+fn start_game(addr: SocketAddr) -> Vec<(TcpStream, Vec<u8>)> {
+    let mut create = vec![2];
+    create.extend(&10u32.to_le_bytes());
+    create.extend(&10u32.to_le_bytes());
+    start_game_with(addr, &create)
+}
+
+/// Starts two player game created with the given CreateLobby payload.
+fn start_game_with(addr: SocketAddr, create: &[u8]) -> Vec<(TcpStream, Vec<u8>)> {
+    let mut players = ready_players(addr, create);
+    for (stream, _) in players.iter_mut() {
+        assert_eq!(receive(stream).0, 19);
+        assert_eq!(receive(stream).0, 20);
+        assert_eq!(receive(stream).0, 14);
+    }
+    players
+}
+
+/// Seats two players in the lobby created with the given CreateLobby
+/// payload and gets them ready, before the game start is pushed.
+fn ready_players(addr: SocketAddr, create: &[u8]) -> Vec<(TcpStream, Vec<u8>)> {
+    let mut players = Vec::new();
+    for name in &["alice", "bob"] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let token = handshake(&mut stream, name);
+        players.push((stream, token));
+    }
+    let (stream, token) = &mut players[0];
+    send_as(stream, token, 4, create);
+    let lobby_id = receive(stream).1[..4].to_vec();
+    let (stream, token) = &mut players[1];
+    send_as(stream, token, 6, &lobby_id);
+    assert_eq!(receive(stream).0, 7);
+    for (stream, token) in players.iter_mut() {
+        send_as(stream, token, 8, &[1]);
+        assert_eq!(receive(stream).0, 9);
+    }
+    players
+}
+
+#[test]
+fn players_order_only_their_own_units() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut players = start_game(addr);
+    let (alice, alice_token) = &mut players[0];
+
+    let mut order = 0u32.to_le_bytes().to_vec();
+    order.extend(&0u32.to_le_bytes());
+    order.extend(&2u32.to_le_bytes());
+    send_as(alice, alice_token, 9, &order);
+    assert_eq!(receive(alice), (10, vec![]));
+
+    // Unit 3 is the bob's one, rejected like the units that don't exist.
+    let mut order = 3u32.to_le_bytes().to_vec();
+    order.extend(&0u32.to_le_bytes());
+    order.extend(&8u32.to_le_bytes());
+    send_as(alice, alice_token, 9, &order);
+    let (id, payload) = receive(alice);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &600u32.to_le_bytes());
+
+    let mut order = 0u32.to_le_bytes().to_vec();
+    order.extend(&100u32.to_le_bytes());
+    order.extend(&100u32.to_le_bytes());
+    send_as(alice, alice_token, 10, &order);
+    let (id, payload) = receive(alice);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &601u32.to_le_bytes());
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn turn_ends_once_every_player_ends_it() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut players = start_game(addr);
+    let mut order = 0u32.to_le_bytes().to_vec();
+    order.extend(&0u32.to_le_bytes());
+    order.extend(&2u32.to_le_bytes());
+
+    let (alice, alice_token) = &mut players[0];
+    send_as(alice, alice_token, 12, &[]);
+    assert_eq!(receive(alice), (11, 0u32.to_le_bytes().to_vec()));
+    send_as(alice, alice_token, 9, &order);
+    let (id, payload) = receive(alice);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &707u32.to_le_bytes());
+
+    let (bob, bob_token) = &mut players[1];
+    send_as(bob, bob_token, 12, &[]);
+    assert_eq!(receive(bob), (11, 0u32.to_le_bytes().to_vec()));
+
+    // Everyone gets the outcome and the start of the next turn.
+    for (stream, _) in players.iter_mut() {
+        let (id, payload) = receive(stream);
+        assert_eq!(id, 12);
+        assert_eq!(&payload[..4], &0u32.to_le_bytes());
+        assert_eq!(&payload[4..8], &6u32.to_le_bytes());
+        let (id, payload) = receive(stream);
+        assert_eq!(id, 14);
+        assert_eq!(&payload[..4], &1u32.to_le_bytes());
+    }
+
+    let (alice, alice_token) = &mut players[0];
+    send_as(alice, alice_token, 9, &order);
+    assert_eq!(receive(alice), (10, vec![]));
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn turn_ends_when_time_runs_out() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.turn_duration_ms = 300;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut players = start_game(addr);
+    let (alice, alice_token) = &mut players[0];
+
+    send_as(alice, alice_token, 12, &[]);
+    assert_eq!(receive(alice), (11, 0u32.to_le_bytes().to_vec()));
+    assert_eq!(receive(alice).0, 12);
+    assert_eq!(receive(alice).0, 14);
+    send_as(alice, alice_token, 12, &[]);
+    assert_eq!(receive(alice), (11, 1u32.to_le_bytes().to_vec()));
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn player_dropped_mid_turn_gets_the_turn_result_on_resume() {
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.shutdown_grace_ms = 100;
+    config.turn_duration_ms = 300;
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut players = start_game(addr);
+    let (bob, bob_token) = players.pop().unwrap();
+    drop(bob);
+    let (alice, alice_token) = &mut players[0];
+    send_as(alice, alice_token, 12, &[]);
+    assert_eq!(receive(alice), (11, 0u32.to_le_bytes().to_vec()));
+    assert_eq!(receive(alice).0, 12);
+    assert_eq!(receive(alice).0, 14);
+
+    let mut bob = TcpStream::connect(addr).unwrap();
+    send(&mut bob, 0, &hello_payload());
+    assert_eq!(receive(&mut bob).0, 1);
+    send(&mut bob, 3, &bob_token);
+    let (id, payload) = receive(&mut bob);
+    assert_eq!(id, 5);
+    let mut missed = Vec::new();
+    let mut rest = &payload[8..];
+    while !rest.is_empty() {
+        let len = u32::from_le_bytes([rest[6], rest[7], rest[8], rest[9]]) as usize;
+        missed.push(u32::from_le_bytes([rest[2], rest[3], rest[4], rest[5]]));
+        rest = &rest[10 + len..];
+    }
+    assert_eq!(&payload[4..8], &(missed.len() as u32).to_le_bytes());
+    // Units of the resolved turn, start of the next one
+    // and the units bob sees now.
+    assert_eq!(missed, vec![12, 14, 20]);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn battles_are_pushed_before_units_moved() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut players = start_game(addr);
+
+    // Alice's cavalry attacks bob's knight.
+    let (alice, alice_token) = &mut players[0];
+    let mut order = 0u32.to_le_bytes().to_vec();
+    order.extend(&1u32.to_le_bytes());
+    order.extend(&9u32.to_le_bytes());
+    send_as(alice, alice_token, 10, &order);
+    assert_eq!(receive(alice), (10, vec![]));
+    for (stream, token) in players.iter_mut() {
+        send_as(stream, token, 12, &[]);
+        assert_eq!(receive(stream).0, 11);
+    }
+
+    let mut battle = Vec::new();
+    for val in &[0u32, 4, 1, 9] {
+        battle.extend(&val.to_le_bytes());
+    }
+    battle.push(0);
+    for (stream, _) in players.iter_mut() {
+        assert_eq!(receive(stream), (16, battle.clone()));
+        let (id, payload) = receive(stream);
+        assert_eq!(id, 12);
+        assert_eq!(&payload[4..8], &5u32.to_le_bytes());
+        assert_eq!(receive(stream).0, 14);
+    }
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn game_ends_in_draw_at_turn_limit_with_equal_scores() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut create = vec![2];
+    create.extend(&10u32.to_le_bytes());
+    create.extend(&10u32.to_le_bytes());
+    create.push(2);
+    create.extend(&1u32.to_le_bytes());
+    let mut players = start_game_with(addr, &create);
+    for (stream, token) in players.iter_mut() {
+        send_as(stream, token, 12, &[]);
+        assert_eq!(receive(stream).0, 11);
+    }
+
+    let mut draw = vec![255];
+    draw.extend(&0u32.to_le_bytes());
+    for (stream, _) in players.iter_mut() {
+        assert_eq!(receive(stream).0, 12);
+        assert_eq!(receive(stream), (13, draw.clone()));
+    }
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn lobby_is_created_from_the_listed_scenario() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = r#"
+players = 2
+board = { width = 3, height = 2, terrain = ".F.\n.~.\n" }
+victory = { kind = "objectives", objectives = [[2, 1]], turns = 1 }
+units = [
+    { owner = 0, category = "knight", position = [0, 0] },
+    { owner = 1, category = "cavalry", position = [2, 0] },
+]
+"#;
+    std::fs::write(dir.path().join("duel.toml"), scenario).unwrap();
+    let mut config = ServerConfig::new(String::from("127.0.0.1"), 0);
+    config.scenario_dir = dir.path().to_str().unwrap().to_string();
+    let (addr, shutdown, thread) = start_server_with(config);
+    let mut alice = TcpStream::connect(addr).unwrap();
+    let alice_token = handshake(&mut alice, "alice");
+    let mut bob = TcpStream::connect(addr).unwrap();
+    let bob_token = handshake(&mut bob, "bob");
+
+    send_as(&mut alice, &alice_token, 13, &[]);
+    let mut listed = 1u32.to_le_bytes().to_vec();
+    listed.extend(&4u32.to_le_bytes());
+    listed.extend(b"duel");
+    listed.push(2);
+    listed.extend(&3u32.to_le_bytes());
+    listed.extend(&2u32.to_le_bytes());
+    listed.push(1);
+    listed.extend(&1u32.to_le_bytes());
+    assert_eq!(receive(&mut alice), (18, listed));
+
+    send_as(&mut alice, &alice_token, 14, b"arena");
+    let (id, payload) = receive(&mut alice);
+    assert_eq!(id, 0);
+    assert_eq!(&payload[..4], &708u32.to_le_bytes());
+
+    send_as(&mut alice, &alice_token, 14, b"duel");
+    let (id, payload) = receive(&mut alice);
+    assert_eq!(id, 7);
+    send_as(&mut bob, &bob_token, 6, &payload[..4]);
+    assert_eq!(receive(&mut bob).0, 7);
+    send_as(&mut alice, &alice_token, 8, &[1]);
+    assert_eq!(receive(&mut alice).0, 9);
+    send_as(&mut bob, &bob_token, 8, &[1]);
+    assert_eq!(receive(&mut bob).0, 9);
+
+    let mut setup = 3u32.to_le_bytes().to_vec();
+    setup.extend(&2u32.to_le_bytes());
+    setup.extend(&[0, 1, 0, 0, 3, 0]);
+    for val in &[1u32, 2, 1] {
+        setup.extend(&val.to_le_bytes());
+    }
+    assert_eq!(receive(&mut alice), (19, setup.clone()));
+    assert_eq!(receive(&mut bob), (19, setup));
+    assert_eq!(receive(&mut alice).0, 20);
+    assert_eq!(receive(&mut bob).0, 20);
+    assert_eq!(receive(&mut alice).0, 14);
+    assert_eq!(receive(&mut bob).0, 14);
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn lobby_with_map_seed_is_played_on_the_generated_map() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut create = vec![2];
+    create.extend(&12u32.to_le_bytes());
+    create.extend(&10u32.to_le_bytes());
+    create.push(0);
+    create.extend(&0u32.to_le_bytes());
+    create.extend(&99u64.to_le_bytes());
+    let mut players = Vec::new();
+    for name in &["alice", "bob"] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let token = handshake(&mut stream, name);
+        players.push((stream, token));
+    }
+    let (stream, token) = &mut players[0];
+    send_as(stream, token, 4, &create);
+    let lobby_id = receive(stream).1[..4].to_vec();
+    let (stream, token) = &mut players[1];
+    send_as(stream, token, 6, &lobby_id);
+    assert_eq!(receive(stream).0, 7);
+    for (stream, token) in players.iter_mut() {
+        send_as(stream, token, 8, &[1]);
+        assert_eq!(receive(stream).0, 9);
+    }
+
+    let map = mapgen::generate(99, (12, 10), 2).map;
+    let mut setup = 12u32.to_le_bytes().to_vec();
+    setup.extend(&10u32.to_le_bytes());
+    for y in 0..10 {
+        for x in 0..12 {
+            setup.push(match map.get((x, y)) {
+                Terrain::Plains => 0,
+                Terrain::Forest => 1,
+                Terrain::Hills => 2,
+                Terrain::Water => 3,
+                Terrain::Wall => 4,
+            });
+        }
+    }
+    setup.extend(&0u32.to_le_bytes());
+    assert!(setup[8..128].iter().any(|&tile| tile != 0));
+    for (stream, _) in players.iter_mut() {
+        assert_eq!(receive(stream), (19, setup.clone()));
+        assert_eq!(receive(stream).0, 20);
+        assert_eq!(receive(stream).0, 14);
+    }
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
+
+#[test]
+fn units_seen_at_the_start_are_ordered_in_the_first_turn() {
+    let (addr, shutdown, thread) = start_server(100);
+    let mut create = vec![2];
+    create.extend(&12u32.to_le_bytes());
+    create.extend(&10u32.to_le_bytes());
+    create.push(0);
+    create.extend(&0u32.to_le_bytes());
+    create.extend(&99u64.to_le_bytes());
+    let mut players = ready_players(addr, &create);
+    let (alice, alice_token) = &mut players[0];
+    assert_eq!(receive(alice).0, 19);
+    let (id, seen) = receive(alice);
+    assert_eq!(id, 20);
+    assert_eq!(&seen[..4], &0u32.to_le_bytes());
+    assert_eq!(receive(alice).0, 14);
+
+    // Alice's army stands where the generated scenario put it.
+    let scenario = mapgen::generate(99, (12, 10), 2);
+    let count = u32::from_le_bytes([seen[4], seen[5], seen[6], seen[7]]) as usize;
+    assert_eq!(seen.len(), 8 + 13 * count);
+    let units: Vec<(u32, u8, (usize, usize))> = seen[8..]
+        .chunks(13)
+        .map(|unit| {
+            let field = |at: usize| {
+                u32::from_le_bytes([unit[at], unit[at + 1], unit[at + 2], unit[at + 3]]) as usize
+            };
+            (field(0) as u32, unit[4], (field(5), field(9)))
+        })
+        .collect();
+    let mut army: Vec<(usize, usize)> = units
+        .iter()
+        .filter(|unit| unit.1 == 0)
+        .map(|unit| unit.2)
+        .collect();
+    let mut deployed: Vec<(usize, usize)> = scenario
+        .units
+        .iter()
+        .filter(|unit| unit.owner_id == 0)
+        .map(|unit| unit.position)
+        .collect();
+    army.sort_unstable();
+    deployed.sort_unstable();
+    assert_eq!(army, deployed);
+
+    // One of them steps off the starting row in the first turn.
+    let (unit_id, _, (x, y)) = *units
+        .iter()
+        .find(|&&(_, owner, (x, y))| {
+            owner == 0
+                && scenario.map.get((x, y + 1)).movement_cost().is_some()
+                && units.iter().all(|other| other.2 != (x, y + 1))
+        })
+        .unwrap();
+    let mut order = unit_id.to_le_bytes().to_vec();
+    order.extend(&(x as u32).to_le_bytes());
+    order.extend(&(y as u32 + 1).to_le_bytes());
+    send_as(alice, alice_token, 9, &order);
+    assert_eq!(receive(alice), (10, vec![]));
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}